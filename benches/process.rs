@@ -0,0 +1,114 @@
+use criterion::BatchSize;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use i_am_freq_remapper::phase_vocoder::InputParams;
+use i_am_freq_remapper::phase_vocoder::PhaseVocoder;
+
+const SAMPLE_RATE: f32 = 44100.0;
+const BLOCK_SIZE: usize = 512;
+const WINDOW_SIZES: [usize; 3] = [512, 2048, 4096];
+const MAPPING_SCRIPT: &str = r#"
+	if frequency > 200.0 {
+		frequency = frequency * (1.0 + a);
+	}
+	magnitude = magnitude * (1.0 - b);
+"#;
+
+fn noise(len: usize) -> Vec<f32> {
+	let mut state = 0x2545_f491_u32;
+	(0..len).map(|_| {
+		state ^= state << 13;
+		state ^= state >> 17;
+		state ^= state << 5;
+		state as f32 / u32::MAX as f32 * 2.0 - 1.0
+	}).collect()
+}
+
+fn input_params(window_size: usize) -> InputParams {
+	InputParams {
+		daw_values: [0.5, 0.25, 0.0, 0.0],
+		window_size,
+		window_factor: 0.5,
+		gain: 1.0,
+		sample_rate: SAMPLE_RATE,
+		..Default::default()
+	}
+}
+
+fn bench_process(c: &mut Criterion) {
+	let input = noise(BLOCK_SIZE);
+	let mut group = c.benchmark_group("process");
+
+	for window_size in WINDOW_SIZES {
+		for (name, code) in [("no_script", ""), ("mapping_script", MAPPING_SCRIPT)] {
+			let params = input_params(window_size);
+			let mut processor = PhaseVocoder::new(window_size, SAMPLE_RATE);
+			processor.update_mapping(code).unwrap();
+			let mut buffer = input.clone();
+
+			group.bench_with_input(BenchmarkId::new(name, window_size), &window_size, |b, _| {
+				b.iter(|| {
+					buffer.copy_from_slice(&input);
+					processor.process(&mut buffer, &params);
+				})
+			});
+		}
+	}
+
+	group.finish();
+}
+
+#[cfg(feature = "parallel")]
+fn bench_channels(c: &mut Criterion) {
+	use rayon::prelude::*;
+
+	let input = noise(BLOCK_SIZE);
+	let params = input_params(2048);
+	let mut group = c.benchmark_group("channels");
+
+	for channel_count in [2, 8] {
+		let mut processors = (0..channel_count).map(|_| {
+			let mut processor = PhaseVocoder::new(2048, SAMPLE_RATE);
+			processor.update_mapping(MAPPING_SCRIPT).unwrap();
+			processor
+		}).collect::<Vec<_>>();
+		let mut buffers = vec![input.clone(); channel_count];
+
+		group.bench_with_input(BenchmarkId::new("serial", channel_count), &channel_count, |b, _| {
+			b.iter(|| {
+				for (buffer, processor) in buffers.iter_mut().zip(processors.iter_mut()) {
+					buffer.copy_from_slice(&input);
+					processor.process(buffer, &params);
+				}
+			})
+		});
+		group.bench_with_input(BenchmarkId::new("parallel", channel_count), &channel_count, |b, _| {
+			b.iter(|| {
+				buffers.par_iter_mut().zip(processors.par_iter_mut()).for_each(|(buffer, processor)| {
+					buffer.copy_from_slice(&input);
+					processor.process(buffer, &params);
+				});
+			})
+		});
+	}
+
+	group.finish();
+}
+
+fn bench_update_mapping(c: &mut Criterion) {
+	c.bench_function("update_mapping", |b| {
+		b.iter_batched(
+			|| PhaseVocoder::new(2048, SAMPLE_RATE),
+			|mut processor| processor.update_mapping(MAPPING_SCRIPT).unwrap(),
+			BatchSize::LargeInput,
+		)
+	});
+}
+
+#[cfg(feature = "parallel")]
+criterion_group!(benches, bench_process, bench_channels, bench_update_mapping);
+#[cfg(not(feature = "parallel"))]
+criterion_group!(benches, bench_process, bench_update_mapping);
+criterion_main!(benches);
@@ -0,0 +1,206 @@
+use std::fmt::Display;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use i_am_freq_remapper::phase_vocoder::InputParams;
+use i_am_freq_remapper::phase_vocoder::PhaseVocoder;
+use hound::SampleFormat;
+use hound::WavSpec;
+use hound::WavReader;
+use hound::WavWriter;
+
+const BLOCK_SIZE: usize = 512;
+const USAGE: &str = "usage: offline <input.wav> <mapper.rhai> <output.wav> [window_size = 2048] [overlap = 4]
+       offline --dump-mapping <mapper.rhai> [a = 0] [b = 0] [c = 0] [d = 0] [window_size = 2048] [sample_rate = 48000]";
+
+struct Options {
+	input: PathBuf,
+	script: PathBuf,
+	output: PathBuf,
+	window_size: usize,
+	overlap: usize,
+}
+
+/// Prints where the script sends every bin instead of processing a file.
+struct MappingOptions {
+	script: PathBuf,
+	daw_values: [f32; 4],
+	window_size: usize,
+	sample_rate: f32,
+}
+
+enum Command {
+	Process(Options),
+	DumpMapping(MappingOptions),
+}
+
+fn parse_arg<T: FromStr>(args: &[String], idx: usize, default: T) -> Result<T, String> where T::Err: Display {
+	match args.get(idx) {
+		Some(arg) => arg.parse().map_err(|e| format!("invalid number `{arg}`: {e}")),
+		None => Ok(default),
+	}
+}
+
+fn parse_options() -> Result<Command, String> {
+	let args = std::env::args().skip(1).collect::<Vec<_>>();
+	if args.first().is_some_and(|arg| arg == "--dump-mapping") {
+		let args = &args[1..];
+		if args.is_empty() {
+			return Err(USAGE.to_string());
+		}
+
+		let mut daw_values = [0.0; 4];
+		for (i, value) in daw_values.iter_mut().enumerate() {
+			*value = parse_arg(args, i + 1, 0.0)?;
+		}
+		return Ok(Command::DumpMapping(MappingOptions {
+			script: PathBuf::from(&args[0]),
+			daw_values,
+			window_size: parse_arg(args, 5, 2048)?,
+			sample_rate: parse_arg(args, 6, 48000.0)?,
+		}));
+	}
+	if args.len() < 3 {
+		return Err(USAGE.to_string());
+	}
+
+	Ok(Command::Process(Options {
+		input: PathBuf::from(&args[0]),
+		script: PathBuf::from(&args[1]),
+		output: PathBuf::from(&args[2]),
+		window_size: parse_arg(&args, 3, 2048)?,
+		overlap: parse_arg(&args, 4, 4)?,
+	}))
+}
+
+fn read_channels(reader: WavReader<impl std::io::Read>) -> Result<Vec<Vec<f32>>, String> {
+	let spec = reader.spec();
+	let samples = match spec.sample_format {
+		SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<Vec<_>, _>>(),
+		SampleFormat::Int => {
+			let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+			reader.into_samples::<i32>().map(|sample| sample.map(|val| val as f32 / scale)).collect()
+		}
+	}.map_err(|e| format!("{e}"))?;
+
+	let channel_count = spec.channels as usize;
+	let mut channels = vec![Vec::with_capacity(samples.len() / channel_count); channel_count];
+	for (i, sample) in samples.into_iter().enumerate() {
+		channels[i % channel_count].push(sample);
+	}
+
+	Ok(channels)
+}
+
+fn run(options: Options) -> Result<(), String> {
+	let reader = WavReader::open(&options.input).map_err(|e| format!("{e}"))?;
+	let spec = reader.spec();
+	if spec.channels == 0 || spec.channels > 2 {
+		return Err(format!("only mono and stereo files are supported, got {} channels", spec.channels));
+	}
+	let sample_rate = spec.sample_rate as f32;
+	let mut channels = read_channels(reader)?;
+
+	let code = std::fs::read_to_string(&options.script).map_err(|e| format!("{e}"))?;
+
+	let mut processors = Vec::with_capacity(channels.len());
+	for _ in 0..channels.len() {
+		let mut processor = PhaseVocoder::with_overlap(options.window_size, options.overlap, sample_rate);
+		processor.update_mapping(&code)?;
+		processors.push(processor);
+	}
+
+	let latency = processors[0].latency();
+	println!("latency: {} samples ({:.2} ms)", latency, latency as f32 / sample_rate * 1000.0);
+
+	let channel_count = channels.len();
+	let input_params = |i: usize, pos: usize| {
+		let time = pos as f32 / sample_rate;
+		InputParams {
+			current_track_id: i,
+			channel_count,
+			playing: true,
+			daw_time: time,
+			sys_time: time,
+			window_size: options.window_size,
+			window_factor: 0.5,
+			gain: 1.0,
+			sample_rate,
+			..Default::default()
+		}
+	};
+
+	let len = channels[0].len();
+	let mut pos = 0;
+	while pos < len {
+		let end = (pos + BLOCK_SIZE).min(len);
+		for (i, (processor, channel)) in processors.iter_mut().zip(channels.iter_mut()).enumerate() {
+			processor.process(&mut channel[pos..end], &input_params(i, pos));
+		}
+
+		pos = end;
+	}
+
+	// the last `latency` samples of input are still inside the vocoder, so the render is that
+	// much longer than the input
+	let mut tail_len = 0;
+	for (i, (processor, channel)) in processors.iter_mut().zip(channels.iter_mut()).enumerate() {
+		channel.resize(len + processor.latency(), 0.0);
+		tail_len = processor.flush(&mut channel[len..], &input_params(i, len));
+	}
+	println!("tail: {} samples", tail_len);
+
+	let out_spec = WavSpec {
+		channels: spec.channels,
+		sample_rate: spec.sample_rate,
+		bits_per_sample: 32,
+		sample_format: SampleFormat::Float,
+	};
+	let mut writer = WavWriter::create(&options.output, out_spec).map_err(|e| format!("{e}"))?;
+	for i in 0..len + tail_len {
+		for channel in &channels {
+			writer.write_sample(channel[i]).map_err(|e| format!("{e}"))?;
+		}
+	}
+	writer.finalize().map_err(|e| format!("{e}"))?;
+
+	Ok(())
+}
+
+/// One CSV row per bin but DC: the bin, its center frequency, and the frequency and magnitude the
+/// script gives it for a magnitude of 1 going in.
+fn dump_mapping(options: MappingOptions) -> Result<(), String> {
+	let code = std::fs::read_to_string(&options.script).map_err(|e| format!("{e}"))?;
+	let mut processor = PhaseVocoder::new(options.window_size, options.sample_rate);
+	processor.update_mapping(&code)?;
+
+	let input_params = InputParams {
+		daw_values: options.daw_values,
+		window_size: options.window_size,
+		window_factor: 0.5,
+		gain: 1.0,
+		sample_rate: options.sample_rate,
+		..Default::default()
+	};
+	let mappings = processor.map_bins(&input_params)?;
+	// written by hand rather than with `println!`, which panics once a pipe like `head` stops reading
+	let mut out = std::io::stdout().lock();
+	writeln!(out, "bin,input_freq,output_freq,output_magnitude").map_err(|e| format!("{e}"))?;
+	for mapping in mappings {
+		writeln!(out, "{},{},{},{}", mapping.bin, mapping.input_frequency, mapping.output_frequency, mapping.output_magnitude)
+			.map_err(|e| format!("{e}"))?;
+	}
+
+	Ok(())
+}
+
+fn main() {
+	let result = parse_options().and_then(|command| match command {
+		Command::Process(options) => run(options),
+		Command::DumpMapping(options) => dump_mapping(options),
+	});
+	if let Err(e) = result {
+		eprintln!("{e}");
+		std::process::exit(1);
+	}
+}
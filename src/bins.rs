@@ -0,0 +1,35 @@
+use std::cell::Cell;
+use rhai::Engine;
+use rhai::FLOAT;
+use rhai::INT;
+
+thread_local! {
+	// like the generator in `rng`, the spacing belongs to the processor running a script, which
+	// lends it to the current thread for the duration of the call
+	static BIN_WIDTH: Cell<f32> = const { Cell::new(0.0) };
+}
+
+/// Runs `f` with bins `bin_width` Hz apart behind `bin_freq` and `freq_to_bin`.
+pub fn with_bin_width<R>(bin_width: f32, f: impl FnOnce() -> R) -> R {
+	let outer = BIN_WIDTH.replace(bin_width);
+	let result = f();
+	BIN_WIDTH.set(outer);
+	result
+}
+
+fn bin_freq(k: FLOAT) -> FLOAT {
+	k * BIN_WIDTH.get()
+}
+
+fn freq_to_bin(frequency: FLOAT) -> FLOAT {
+	let bin_width = BIN_WIDTH.get();
+	if bin_width > 0.0 { frequency / bin_width } else { 0.0 }
+}
+
+/// Lets scripts turn any bin's index into its center frequency and back, with the spacing of the
+/// frame being processed, zero padding included.
+pub fn register(engine: &mut Engine) {
+	engine.register_fn("bin_freq", |k: INT| bin_freq(k as FLOAT));
+	engine.register_fn("bin_freq", bin_freq);
+	engine.register_fn("freq_to_bin", freq_to_bin);
+}
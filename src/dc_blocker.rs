@@ -0,0 +1,25 @@
+use std::f32::consts::PI;
+
+/// A one-pole high-pass for the plugin's output, one per channel. Takes out the DC and subsonic
+/// rumble a mapping can leave behind, bins moved down to 0 Hz most of all, and leaves anything
+/// well above the cutoff alone.
+#[derive(Default)]
+pub struct DcBlocker {
+	prev_input: f32,
+	prev_output: f32,
+}
+
+impl DcBlocker {
+	/// Filters one sample, with the cutoff in Hz.
+	pub fn next(&mut self, input: f32, cutoff: f32, sample_rate: f32) -> f32 {
+		let coeff = (-2.0 * PI * cutoff / sample_rate.max(1.0)).exp();
+		let output = input - self.prev_input + coeff * self.prev_output;
+		self.prev_input = input;
+		self.prev_output = output;
+		output
+	}
+
+	pub fn reset(&mut self) {
+		*self = Self::default();
+	}
+}
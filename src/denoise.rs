@@ -0,0 +1,64 @@
+use realfft::num_complex::Complex;
+
+/// A noise print for spectral subtraction: the average magnitude of every bin over the frames heard
+/// while learning. Each processor learns its own, so every channel takes away its own noise.
+pub struct NoiseProfile {
+	// summed over `frames`, so a profile can keep growing for as long as learning is held
+	magnitude: Vec<f32>,
+	frames: usize,
+	learning: bool,
+}
+
+impl NoiseProfile {
+	/// Room for `spectrum_size` bins, which has to cover the largest spectrum that will be learned.
+	pub fn new(spectrum_size: usize) -> Self {
+		Self {
+			magnitude: vec![0.0; spectrum_size],
+			frames: 0,
+			learning: false,
+		}
+	}
+
+	/// Whether there's a profile to subtract: something was learned and learning is over.
+	pub fn is_ready(&self) -> bool {
+		!self.learning && self.frames > 0
+	}
+
+	/// Adds this frame to the profile while `learn` is held. Holding it again starts a new profile.
+	pub fn learn(&mut self, spectrum: &[Complex<f32>], learn: bool) {
+		if learn && !self.learning {
+			self.clear();
+		}
+		self.learning = learn;
+		if !learn {
+			return;
+		}
+
+		for (sum, value) in self.magnitude.iter_mut().zip(spectrum) {
+			*sum += value.norm();
+		}
+		self.frames += 1;
+	}
+
+	/// Takes `amount` times the profile off every bin's magnitude, keeping its phase. Bins don't go
+	/// below silence, so an `amount` over 1 gates the noise that rises above its average.
+	pub fn subtract(&self, spectrum: &mut [Complex<f32>], amount: f32) {
+		if !self.is_ready() || amount <= 0.0 {
+			return;
+		}
+
+		let scale = amount / self.frames as f32;
+		for (value, sum) in spectrum.iter_mut().zip(&self.magnitude) {
+			let magnitude = value.norm();
+			if magnitude > 0.0 {
+				*value *= (magnitude - sum * scale).max(0.0) / magnitude;
+			}
+		}
+	}
+
+	/// Forgets the profile, as when the bins it was learned for no longer exist.
+	pub fn clear(&mut self) {
+		self.magnitude.fill(0.0);
+		self.frames = 0;
+	}
+}
@@ -0,0 +1,57 @@
+use std::io::Read;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+
+// scripts are a few KB at most; anything much bigger is not a script
+const MAX_SCRIPT_BYTES: u64 = 256 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A script download on its own thread, started by the editor and polled by it every frame.
+/// It never touches the params: the editor puts the script in place itself, like Load does.
+pub struct ScriptDownload {
+	// behind a mutex since the editor's state has to be `Sync`, which a receiver isn't
+	result: Mutex<Receiver<Result<String, String>>>,
+}
+
+impl ScriptDownload {
+	pub fn start(url: &str) -> Self {
+		let url = url.trim().to_string();
+		let (sender, result) = mpsc::channel();
+		std::thread::spawn(move || {
+			// the editor may have been closed by now, and then nobody is waiting
+			let _ = sender.send(fetch(&url));
+		});
+		Self { result: Mutex::new(result) }
+	}
+
+	/// The script, or what went wrong, once the download is over.
+	pub fn take(&self) -> Option<Result<String, String>> {
+		match self.result.lock().unwrap_or_else(PoisonError::into_inner).try_recv() {
+			Ok(result) => Some(result),
+			Err(TryRecvError::Empty) => None,
+			Err(TryRecvError::Disconnected) => Some(Err(String::from("the download stopped unexpectedly"))),
+		}
+	}
+}
+
+fn fetch(url: &str) -> Result<String, String> {
+	if !url.starts_with("https://") && !url.starts_with("http://") {
+		return Err(format!("`{url}` is not an http:// or https:// address"));
+	}
+
+	let response = ureq::get(url).timeout(TIMEOUT).call().map_err(|err| format!("{}", err))?;
+	let mut bytes = Vec::new();
+	response.into_reader()
+		.take(MAX_SCRIPT_BYTES + 1)
+		.read_to_end(&mut bytes)
+		.map_err(|err| format!("{}", err))?;
+	if bytes.len() as u64 > MAX_SCRIPT_BYTES {
+		return Err(format!("larger than {} KB, which is too big for a script", MAX_SCRIPT_BYTES / 1024));
+	}
+
+	String::from_utf8(bytes).map_err(|_| String::from("not a text file"))
+}
@@ -0,0 +1,147 @@
+use std::fmt::Write;
+use std::hash::BuildHasher;
+use std::hash::BuildHasherDefault;
+use std::hash::DefaultHasher;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use time::OffsetDateTime;
+use crate::spectrum::SpectrumCapture;
+
+/// A captured spectrum being written to a CSV file on its own thread, started by the editor and
+/// polled by it every frame, the same way as a script download.
+pub struct SpectrumExport {
+	// behind a mutex since the editor's state has to be `Sync`, which a receiver isn't
+	result: Mutex<Receiver<Result<PathBuf, String>>>,
+}
+
+impl SpectrumExport {
+	/// Writes `capture` with `header` on top to a new file in the documents folder.
+	pub fn start(capture: SpectrumCapture, header: Vec<(String, String)>) -> Self {
+		let (sender, result) = mpsc::channel();
+		std::thread::spawn(move || {
+			let written = create_export(&export_dir(), OffsetDateTime::now_utc().unix_timestamp_nanos())
+				.and_then(|(path, mut file)| file.write_all(to_csv(&capture, &header).as_bytes()).map(|()| path))
+				.map_err(|err| format!("{}", err));
+			// the editor may have been closed by now, and then nobody is waiting
+			let _ = sender.send(written);
+		});
+		Self { result: Mutex::new(result) }
+	}
+
+	/// Where the file went, or what went wrong, once it's written.
+	pub fn take(&self) -> Option<Result<PathBuf, String>> {
+		match self.result.lock().unwrap_or_else(PoisonError::into_inner).try_recv() {
+			Ok(result) => Some(result),
+			Err(TryRecvError::Empty) => None,
+			Err(TryRecvError::Disconnected) => Some(Err(String::from("the export stopped unexpectedly"))),
+		}
+	}
+}
+
+fn export_dir() -> PathBuf {
+	dirs::document_dir()
+		.or_else(|| std::fs::canonicalize(".").ok())
+		.unwrap_or_else(|| PathBuf::from("."))
+}
+
+// named after the time `stamp` in nanoseconds, with a counter on top if that name is taken, and
+// only ever created new, so exports don't overwrite each other or anything else
+fn create_export(dir: &Path, stamp: i128) -> io::Result<(PathBuf, File)> {
+	let mut attempt = 0_u32;
+	loop {
+		let name = if attempt == 0 {
+			format!("i_am_freq_remapper_spectrum_{stamp}.csv")
+		}else {
+			format!("i_am_freq_remapper_spectrum_{stamp}_{attempt}.csv")
+		};
+		let path = dir.join(name);
+		match OpenOptions::new().write(true).create_new(true).open(&path) {
+			Ok(file) => return Ok((path, file)),
+			Err(err) if err.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+			Err(err) => return Err(err),
+		}
+	}
+}
+
+/// A hash of a script for the export's header. Unlike the one the vocoder spots changes with,
+/// it's the same from run to run, so two reports made with the same script can be told apart
+/// from two made with different ones.
+pub fn script_hash(code: &str) -> u64 {
+	BuildHasherDefault::<DefaultHasher>::default().hash_one(code)
+}
+
+/// `capture` as CSV: a `#` line for the sample rate and for each of `header`'s names and values,
+/// then a row for every bin with its index, frequency in Hz and input and output magnitude.
+pub fn to_csv(capture: &SpectrumCapture, header: &[(String, String)]) -> String {
+	let mut csv = String::new();
+	let _ = writeln!(csv, "# sample_rate = {}", capture.sample_rate);
+	for (name, value) in header {
+		let _ = writeln!(csv, "# {name} = {value}");
+	}
+
+	csv.push_str("bin,frequency,input,output\n");
+	let bins = capture.input.len();
+	for (k, (input, output)) in capture.input.iter().zip(&capture.output).enumerate() {
+		// the same spacing as the bins of the frame, zero padding included
+		let frequency = k as f32 * capture.sample_rate / (2 * bins.saturating_sub(1)).max(1) as f32;
+		let _ = writeln!(csv, "{k},{frequency},{input},{output}");
+	}
+	csv
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn exports_in_the_same_instant_get_their_own_files() {
+		let dir = std::env::temp_dir().join(format!("i_am_freq_remapper_export_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+
+		let (first, mut file) = create_export(&dir, 42).unwrap();
+		file.write_all(b"first").unwrap();
+		let taken = dir.join("i_am_freq_remapper_spectrum_42_1.csv");
+		std::fs::write(&taken, "taken").unwrap();
+
+		let paths: Vec<PathBuf> = (0..3).map(|_| create_export(&dir, 42).unwrap().0).collect();
+		assert_eq!(first, dir.join("i_am_freq_remapper_spectrum_42.csv"));
+		assert_eq!(paths, [2, 3, 4].map(|n| dir.join(format!("i_am_freq_remapper_spectrum_42_{n}.csv"))));
+		assert_eq!(std::fs::read_to_string(&first).unwrap(), "first");
+		assert_eq!(std::fs::read_to_string(&taken).unwrap(), "taken");
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn csv_reads_back_as_what_was_captured() {
+		let capture = SpectrumCapture {
+			sample_rate: 48000.0,
+			input: (0..1025).map(|k| k as f32 / 7.0).collect(),
+			output: (0..1025).map(|k| 1.0 / (1.0 + k as f32)).collect(),
+		};
+		let header = vec![
+			(String::from("version"), String::from("0.1.1")),
+			(String::from("window_size"), String::from("2048")),
+		];
+		let csv = to_csv(&capture, &header);
+
+		let (comments, rest): (Vec<&str>, Vec<&str>) = csv.lines().partition(|line| line.starts_with('#'));
+		assert_eq!(comments, ["# sample_rate = 48000", "# version = 0.1.1", "# window_size = 2048"]);
+		assert_eq!(rest[0], "bin,frequency,input,output");
+		let rows = &rest[1..];
+		assert_eq!(rows.len(), capture.input.len());
+		for (k, row) in rows.iter().enumerate() {
+			let fields: Vec<f32> = row.split(',').map(|field| field.parse().unwrap()).collect();
+			assert_eq!(fields, [k as f32, k as f32 * 48000.0 / 2048.0, capture.input[k], capture.output[k]], "row {k}");
+		}
+	}
+}
@@ -0,0 +1,126 @@
+/// Where a spectral freeze is in its cycle. It's held while the freeze param is on or the pedal is down:
+///
+/// `Idle` → held → `Capture` → next frame → `Hold` → released → `Release` → faded out → `Idle`
+///
+/// Holding again during `Release` captures anew from what is heard at that moment, so catching the
+/// next chord doesn't wait for the last one to fade.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FreezeState {
+	Idle,
+	Capture,
+	Hold,
+	Release,
+}
+
+/// The freeze state machine and its crossfade. Each processor runs its own, and they stay in step
+/// because every channel sees the same frames and the same held state.
+pub struct Freeze {
+	state: FreezeState,
+	// 0 is the live spectrum, 1 the frozen one
+	mix: f32,
+}
+
+impl Default for Freeze {
+	fn default() -> Self {
+		Self {
+			state: FreezeState::Idle,
+			mix: 0.0,
+		}
+	}
+}
+
+impl Freeze {
+	pub fn state(&self) -> FreezeState {
+		self.state
+	}
+
+	pub fn mix(&self) -> f32 {
+		self.mix
+	}
+
+	/// Follows the param or pedal. Called once per frame, before [`Freeze::next_frame`].
+	pub fn set_held(&mut self, held: bool) {
+		self.state = match (self.state, held) {
+			(FreezeState::Idle | FreezeState::Release, true) => FreezeState::Capture,
+			// let go before anything was captured
+			(FreezeState::Capture, false) if self.mix > 0.0 => FreezeState::Release,
+			(FreezeState::Capture, false) => FreezeState::Idle,
+			(FreezeState::Hold, false) => FreezeState::Release,
+			(state, _) => state,
+		};
+	}
+
+	/// Moves on by one frame, with `step` the part of the crossfade a frame covers.
+	/// Returns whether this frame is the one to capture, which is taken before the mix moves.
+	pub fn next_frame(&mut self, step: f32) -> bool {
+		match self.state {
+			FreezeState::Idle => false,
+			FreezeState::Capture => {
+				self.state = FreezeState::Hold;
+				self.mix = (self.mix + step).min(1.0);
+				true
+			},
+			FreezeState::Hold => {
+				self.mix = (self.mix + step).min(1.0);
+				false
+			},
+			FreezeState::Release => {
+				self.mix = (self.mix - step).max(0.0);
+				if self.mix <= 0.0 {
+					self.state = FreezeState::Idle;
+				}
+				false
+			},
+		}
+	}
+
+	pub fn clear(&mut self) {
+		*self = Self::default();
+	}
+}
+
+/// Which of the two spectra a freeze morphs between.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MorphSlot {
+	A,
+	B,
+}
+
+impl MorphSlot {
+	pub const ALL: [MorphSlot; 2] = [MorphSlot::A, MorphSlot::B];
+}
+
+/// A spectrum taken for the freeze to morph from or to, kept the way a frozen one is: each bin's
+/// magnitude, its phase when taken, and how far it turns per sample. Sized for the largest window
+/// up front, so taking one never allocates.
+pub struct Snapshot {
+	pub magnitude: Vec<f32>,
+	pub phase: Vec<f32>,
+	pub advance: Vec<f32>,
+	captured: bool,
+}
+
+impl Snapshot {
+	pub fn new(bins: usize) -> Self {
+		Self {
+			magnitude: vec![0.0; bins],
+			phase: vec![0.0; bins],
+			advance: vec![0.0; bins],
+			captured: false,
+		}
+	}
+
+	pub fn captured(&self) -> bool {
+		self.captured
+	}
+
+	/// Marks the bins written since as a whole spectrum.
+	pub fn set_captured(&mut self) {
+		self.captured = true;
+	}
+
+	/// Forgets the spectrum; the bins are only overwritten by the next capture.
+	pub fn clear(&mut self) {
+		self.captured = false;
+	}
+}
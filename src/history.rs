@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+use time::OffsetDateTime;
+use crate::Arguments;
+use crate::ScriptSlot;
+
+const HISTORY_DEPTH: usize = 32;
+
+/// Scripts replaced from the editor, so a stray Load, Clear or drop can be taken back.
+/// Kept in the editor state only, never saved with the project.
+#[derive(Default)]
+pub struct CodeHistory {
+	undo: VecDeque<(ScriptSlot, Result<String, String>)>,
+	redo: Vec<(ScriptSlot, Result<String, String>)>,
+}
+
+impl CodeHistory {
+	/// Swaps in `code` for `slot` and remembers the script it replaced. Clears the redo side.
+	pub fn replace(&mut self, params: &Arguments, slot: ScriptSlot, code: Result<String, String>) {
+		let old = swap(params, slot, code);
+		if self.undo.len() == HISTORY_DEPTH {
+			self.undo.pop_front();
+		}
+		self.undo.push_back((slot, old));
+		self.redo.clear();
+	}
+
+	/// Takes back the last change, to whichever slot it went.
+	pub fn undo(&mut self, params: &Arguments) {
+		if let Some((slot, code)) = self.undo.pop_back() {
+			self.redo.push((slot, swap(params, slot, code)));
+		}
+	}
+
+	pub fn redo(&mut self, params: &Arguments) {
+		if let Some((slot, code)) = self.redo.pop() {
+			self.undo.push_back((slot, swap(params, slot, code)));
+		}
+	}
+
+	pub fn can_undo(&self) -> bool {
+		!self.undo.is_empty()
+	}
+
+	pub fn can_redo(&self) -> bool {
+		!self.redo.is_empty()
+	}
+}
+
+/// The script is picked up at the start of the next block, the same as after Load.
+fn swap(params: &Arguments, slot: ScriptSlot, code: Result<String, String>) -> Result<String, String> {
+	let old = std::mem::replace(&mut *params.script(slot).write().unwrap(), code);
+	*params.date.write().unwrap() = OffsetDateTime::now_utc().to_string();
+	old
+}
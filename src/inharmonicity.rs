@@ -0,0 +1,28 @@
+use rhai::Engine;
+use rhai::FLOAT;
+
+/// Where `frequency` goes in a stiff string's stretched series over `fundamental`, with
+/// `coefficient` the string's inharmonicity B. Partial n of a stiff string sits at
+/// `n * f * sqrt(1 + B * n^2)`; this is taken relative to the first partial, so `fundamental`
+/// itself stays where it is and partial n is moved by `sqrt((1 + B * n^2) / (1 + B))`.
+///
+/// Which partial `frequency` is comes from the nearest multiple of `fundamental`, at least the
+/// first, and every bin of that partial is moved by the same ratio so its shape survives. A
+/// coefficient of 0 or a `fundamental` that isn't above 0 leaves `frequency` alone. A negative one
+/// pulls the partials together instead, and past the partial where `1 + B * n^2` reaches 0 there's
+/// nowhere left to go: those come back as NaN, which the vocoder leaves out.
+pub fn stretch_tune(frequency: FLOAT, fundamental: FLOAT, coefficient: FLOAT) -> FLOAT {
+	if !(fundamental.is_finite() && fundamental > 0.0) {
+		return frequency;
+	}
+	let partial = (frequency / fundamental).round().max(1.0);
+	let stretch = (1.0 + coefficient * partial * partial) / (1.0 + coefficient);
+	if stretch.is_nan() || stretch <= 0.0 {
+		return FLOAT::NAN;
+	}
+	frequency * stretch.sqrt()
+}
+
+pub fn register(engine: &mut Engine) {
+	engine.register_fn("stretch_tune", stretch_tune);
+}
@@ -1,20 +1,107 @@
 pub mod phase_vocoder;
 pub mod ring_buffer;
+pub mod spectrum;
+pub mod meter;
+pub mod rng;
+pub mod bins;
+pub mod inharmonicity;
+pub mod preset;
+pub mod history;
+pub mod osc;
+pub mod notes;
+pub mod freeze;
+pub mod triple_buffer;
+pub mod limiter;
+pub mod midi_learn;
+pub mod tuning;
+pub mod dc_blocker;
+pub mod denoise;
+pub mod scale;
+pub mod script_api;
+pub mod export;
+#[cfg(feature = "url")]
+pub mod download;
 
 use std::path::PathBuf;
 use time::OffsetDateTime;
 use std::sync::RwLock;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
 use crate::egui::Vec2;
+use crate::phase_vocoder::BANDS;
+use crate::phase_vocoder::DB_FLOOR;
 use crate::phase_vocoder::InputParams;
 use crate::phase_vocoder::PhaseVocoder;
+use crate::phase_vocoder::MAX_STRETCH;
+use crate::phase_vocoder::MAX_WINDOW_SIZE;
+use crate::phase_vocoder::MAX_ZERO_PAD;
+use crate::phase_vocoder::OVERLAP_RATIO;
+use crate::phase_vocoder::RangeMode;
+use crate::phase_vocoder::SpectralDynamics;
+use crate::phase_vocoder::OutOfBand;
+use crate::phase_vocoder::latency_for;
+use crate::phase_vocoder::flattest_window_factor;
+use crate::phase_vocoder::overlap_add;
+use crate::spectrum::SpectrumData;
+use crate::meter::Level;
+use crate::meter::Meters;
+use crate::preset::Preset;
+use crate::preset::AbSlots;
+use crate::history::CodeHistory;
+use crate::osc::OscSettings;
+use crate::midi_learn::CcBindings;
+use crate::midi_learn::MidiLearn;
+use crate::midi_learn::LEARNABLE;
+use crate::freeze::MorphSlot;
+use crate::tuning::Transposition;
+use crate::notes::HeldNotes;
+use crate::limiter::Limiter;
+use crate::dc_blocker::DcBlocker;
+use crate::scale::NOTE_NAMES;
+use crate::script_api::ApiEntry;
+use crate::scale::MAJOR;
 use nih_plug_egui::widgets::ParamSlider;
 use nih_plug_egui::egui;
 use nih_plug_egui::create_egui_editor;
+use nih_plug_egui::resizable_window::ResizableWindow;
 use nih_plug_egui::EguiState;
 use std::sync::Arc;
 use nih_plug::prelude::*;
+use nih_plug::params::persist::PersistentField;
+use nih_plug::wrapper::state::ParamValue;
+use nih_plug::wrapper::state::PluginState;
 
 const WINDOW_SIZE: usize = 2048;
+const EDITOR_WIDTH: u32 = 540;
+const EDITOR_HEIGHT: u32 = 400;
+const SMOOTHING_MS: f32 = 50.0;
+
+const SPECTRUM_HEIGHT: f32 = 100.0;
+const SPECTRUM_MIN_FREQUENCY: f32 = 20.0;
+const SPECTRUM_MIN_DB: f32 = -100.0;
+const METER_WIDTH: f32 = 110.0;
+const METER_HEIGHT: f32 = 8.0;
+const METER_MIN_DB: f32 = -60.0;
+const KEY_WIDTH: f32 = 18.0;
+const KEY_HEIGHT: f32 = 48.0;
+const LATENCY_WARNING_MS: f32 = 150.0;
+const LOAD_METER_WIDTH: f32 = 40.0;
+// above this share of the block time the load turns yellow, and red past all of it
+const LOAD_WARNING: f32 = 0.7;
+// the gain reduction bar is full at this many dB
+const GAIN_REDUCTION_RANGE_DB: f32 = 12.0;
+// how far the overlapped window may swing over a hop, relative to its mean, before the editor warns
+const OVERLAP_RIPPLE_WARNING: f32 = 0.01;
+// `max_freq` all the way up processes every bin, whatever the sample rate
+const MAX_PROCESSED_FREQ: f32 = 20000.0;
+const EXAMPLES: [&str; 5] = [
+	include_str!("../scripts/octave_up.rhai"),
+	include_str!("../scripts/frequency_shift.rhai"),
+	include_str!("../scripts/scale_snap.rhai"),
+	include_str!("../scripts/spectral_gate.rhai"),
+	include_str!("../scripts/chord_snap.rhai"),
+];
 
 #[cfg(feature = "zh_cn_support")]
 const FONT: &[u8; 7094212] = include_bytes!("../LXGWNeoXiHei.ttf");
@@ -33,39 +120,72 @@ lazy_static::lazy_static! {
 	};
 }
 
-struct Interface {
+pub struct Interface {
 	pub params: Arc<Arguments>,
 
 	instant: OffsetDateTime,
-	processor: [Option<PhaseVocoder>; 2],
+	processor: Vec<PhaseVocoder>,
+	spectrum: Arc<SpectrumData>,
+	meters: Arc<Meters>,
+	notes: HeldNotes,
+	// whether the CC set by `freeze_cc` is down, as a sustain pedal
+	freeze_pedal: bool,
+	// the macros a learned CC is driving instead of their param
+	cc_takeovers: [Option<CcTakeover>; LEARNABLE],
+	limiter: Limiter,
+	dc_blockers: Vec<DcBlocker>,
 }
 
-cfg_if::cfg_if! {
-	if #[cfg(all(feature = "en_us", feature = "zh_cn"))] {
-		#[derive(Default, PartialEq)]
-		enum Language {
-			#[default] ZhCn,
-			EnUs,
-		}
+/// A learned CC driving a macro on the audio thread, smoothed like the param, until the param
+/// itself moves again.
+struct CcTakeover {
+	// what the param was when the CC took over; automation, OSC or the editor moving it hands the
+	// macro back
+	param_value: f32,
+	smoother: Smoother<f32>,
+}
 
-		#[derive(Default)]
-		struct GuiInfo {
-			show_code: bool,
-			language: Language,
-		}
-	}else if #[cfg(feature = "zh_cn")] {
-		#[derive(Default)]
-		struct GuiInfo {
-			show_code: bool,
-		}
-	}else if #[cfg(feature = "en_us")] {
-		#[derive(Default)]
-		struct GuiInfo {
-			show_code: bool,
-		}
-	}else {
-		compile_error!{"At least one language must be set."}
-	}
+/// The editor's language, only picked in a build with both.
+#[derive(Default, PartialEq, Clone, Copy, serde::Serialize, serde::Deserialize)]
+enum Language {
+	#[default] ZhCn,
+	EnUs,
+}
+
+#[cfg(not(any(feature = "en_us", feature = "zh_cn")))]
+compile_error!{"At least one language must be set."}
+
+#[derive(Default)]
+struct GuiInfo {
+	show_code: bool,
+	linear_frequency: bool,
+	linear_magnitude: bool,
+	#[cfg(all(feature = "en_us", feature = "zh_cn"))]
+	language: Language,
+	preset_name: String,
+	presets: Vec<String>,
+	preset_status: String,
+	ab: AbSlots,
+	persist_ab: bool,
+	history: CodeHistory,
+	script_slot: ScriptSlot,
+	// whether the Learn Noise button is what's holding `learn_noise` on
+	learning_noise: bool,
+	spectrum_export: Option<export::SpectrumExport>,
+	export_status: String,
+	tuning: tuning::TuningProbe,
+	#[cfg(feature = "osc")]
+	osc: Option<osc::OscServer>,
+	#[cfg(feature = "osc")]
+	osc_port: Option<u16>,
+	#[cfg(feature = "osc")]
+	osc_status: String,
+	#[cfg(feature = "url")]
+	script_url: String,
+	#[cfg(feature = "url")]
+	download: Option<(ScriptSlot, download::ScriptDownload)>,
+	#[cfg(feature = "url")]
+	download_status: String,
 }
 
 impl Default for Interface {
@@ -74,6 +194,13 @@ impl Default for Interface {
 			params: Default::default(),
 			instant: OffsetDateTime::now_utc(),
 			processor: Default::default(),
+			spectrum: Default::default(),
+			meters: Default::default(),
+			notes: Default::default(),
+			freeze_pedal: false,
+			cc_takeovers: Default::default(),
+			limiter: Default::default(),
+			dc_blockers: Vec::new(),
 		}
 	}
 }
@@ -91,21 +218,172 @@ pub struct Arguments  {
 	pub c: FloatParam,
 	#[id = "d"]
 	pub d: FloatParam,
+	/// The scale picked on the editor's keyboard, for scripts that snap to one.
+	#[id = "scale_root"]
+	pub scale_root: IntParam,
+	#[id = "scale"]
+	pub scale: IntParam,
 
 	#[id = "gain"]
 	pub gain: FloatParam,
+	#[id = "bypass"]
+	pub bypass: BoolParam,
+	#[id = "width"]
+	pub width: FloatParam,
+	#[id = "decorrelation"]
+	pub decorrelation: FloatParam,
+	#[id = "ms_mode"]
+	pub ms_mode: BoolParam,
+	#[id = "limiter"]
+	pub limiter: BoolParam,
+	#[id = "limiter_threshold"]
+	pub limiter_threshold: FloatParam,
+	#[id = "limiter_release"]
+	pub limiter_release: FloatParam,
+	#[id = "dc_block"]
+	pub dc_block: BoolParam,
+	#[id = "dc_cutoff"]
+	pub dc_cutoff: FloatParam,
+	#[id = "freeze"]
+	pub freeze: BoolParam,
+	#[id = "freeze_cc"]
+	pub freeze_cc: IntParam,
+	/// Where a freeze sits between the editor's snapshots A and B, once both are taken.
+	#[id = "morph"]
+	pub morph: FloatParam,
+	/// Seconds a bin held at its loudest takes to fall by 60 dB, see `PhaseVocoder::apply_hold`.
+	#[id = "hold_time"]
+	pub hold_time: FloatParam,
+	/// On while the editor's Learn Noise button is held down, or while the host automates it on.
+	#[id = "learn_noise"]
+	pub learn_noise: BoolParam,
+	#[id = "denoise"]
+	pub denoise: FloatParam,
+	#[id = "pitch_shift"]
+	pub pitch_shift: FloatParam,
+	#[id = "freq_shift"]
+	pub freq_shift: FloatParam,
+	#[id = "preserve_formants"]
+	pub preserve_formants: BoolParam,
+	#[id = "whisper"]
+	pub whisper: FloatParam,
+	#[id = "robotize"]
+	pub robotize: BoolParam,
+	#[id = "smoothing"]
+	pub smoothing: FloatParam,
+	#[id = "tilt"]
+	pub tilt: FloatParam,
+	#[id = "tilt_pivot"]
+	pub tilt_pivot: FloatParam,
+	#[id = "slew"]
+	pub slew: FloatParam,
+	#[id = "blur"]
+	pub blur: FloatParam,
+	#[id = "spread"]
+	pub spread: FloatParam,
+	#[id = "stretch"]
+	pub stretch: FloatParam,
+	#[id = "transient_sensitivity"]
+	pub transient_sensitivity: FloatParam,
+	#[id = "sustain_attack"]
+	pub sustain_attack: FloatParam,
+	#[id = "sustain_release"]
+	pub sustain_release: FloatParam,
+	#[id = "harmonics"]
+	pub harmonics: IntParam,
+	#[id = "harmonic_rolloff"]
+	pub harmonic_rolloff: FloatParam,
+	#[id = "spectral_threshold"]
+	pub spectral_threshold: FloatParam,
+	#[id = "spectral_ratio"]
+	pub spectral_ratio: FloatParam,
+	#[id = "spectral_dynamics"]
+	pub spectral_dynamics: EnumParam<SpectralDynamics>,
+	#[id = "spectral_makeup"]
+	pub spectral_makeup: FloatParam,
 
-	#[id = "window_size"]
+	#[id = "seed"]
+	pub seed: IntParam,
+	#[id = "magnitude_in_db"]
+	pub magnitude_in_db: BoolParam,
+	#[id = "peak_interpolation"]
+	pub peak_interpolation: BoolParam,
+	#[id = "phase_lock"]
+	pub phase_lock: BoolParam,
+	#[id = "preserve_energy"]
+	pub preserve_energy: BoolParam,
+	#[id = "auto_makeup"]
+	pub auto_makeup: BoolParam,
+	#[id = "range_mode"]
+	pub range_mode: EnumParam<RangeMode>,
+
+	// under a new ID since it goes past 4096: widening the old one's range in place would have
+	// changed what every normalized value, and so every automation recorded against it, stands for
+	#[id = "window_size_log2"]
 	pub window_size: IntParam,
+	/// `window_size` as it was up to 4096, hidden and kept so automation recorded against it still
+	/// plays back, see [`Arguments::window_samples`].
+	#[id = "window_size"]
+	pub legacy_window_size: IntParam,
+	/// Set the first time anything moves `legacy_window_size`, after which it decides the size.
+	pub legacy_window_size_moved: Arc<AtomicBool>,
+	#[id = "zero_pad"]
+	pub zero_pad: IntParam,
+	#[id = "low_latency"]
+	pub low_latency: BoolParam,
 	#[id = "window_offset"]
-	pub window_offset: IntParam,
+	pub window_offset: FloatParam,
 	#[id = "window_factor"]
 	pub window_factor: FloatParam,
+	#[id = "script_window"]
+	pub script_window: BoolParam,
+	#[id = "crossover_low"]
+	pub crossover_low: FloatParam,
+	#[id = "crossover_high"]
+	pub crossover_high: FloatParam,
+	#[id = "min_freq"]
+	pub min_freq: FloatParam,
+	#[id = "max_freq"]
+	pub max_freq: FloatParam,
+	#[id = "out_of_band"]
+	pub out_of_band: EnumParam<OutOfBand>,
 
 	#[persist = "map_code"]
 	pub map_code: RwLock<Result<String, String>>,
+	/// Run by the second channel instead of `map_code` unless it's empty.
+	#[persist = "right_map_code"]
+	pub right_map_code: RwLock<Result<String, String>>,
+	/// Run by every channel's bins below `crossover_low`, between the crossovers and above
+	/// `crossover_high`, instead of the channel's own script unless they're empty.
+	#[persist = "low_band_code"]
+	pub low_band_code: RwLock<Result<String, String>>,
+	#[persist = "mid_band_code"]
+	pub mid_band_code: RwLock<Result<String, String>>,
+	#[persist = "high_band_code"]
+	pub high_band_code: RwLock<Result<String, String>>,
 	#[persist = "update_date"]
 	pub date: RwLock<String>,
+	/// Only `Some` while the editor's "keep with project" box is ticked.
+	#[persist = "ab_slots"]
+	pub ab_slots: RwLock<Option<AbSlots>>,
+	/// Kept even when built without the `osc` feature, so projects move between builds intact.
+	#[persist = "osc"]
+	pub osc: RwLock<OscSettings>,
+	/// The CCs learned for `a`..`d`.
+	#[persist = "midi_cc"]
+	pub midi_learn: MidiLearn,
+	/// Set by the editor's Panic button, taken by the next `process` call.
+	pub panic: AtomicBool,
+	/// Set by the editor's A and B buttons, taken by the next `process` call.
+	pub take_snapshot: [AtomicBool; 2],
+	/// Whether the processors hold snapshot A and B, for the editor.
+	pub snapshot_taken: [AtomicBool; 2],
+	/// The first error a script ran into while processing, until a script is loaded. Not saved.
+	pub script_error: RwLock<Option<String>>,
+
+	/// Kept even when built with a single language, so projects move between builds intact.
+	#[persist = "language"]
+	language: Arc<RwLock<Language>>,
 }
 
 impl Default for Arguments {
@@ -114,40 +392,329 @@ impl Default for Arguments {
 			FloatParam::new(name, 0.0, FloatRange::Linear{ 
 				min: 0.0, 
 				max: 1.0 
-			}).with_value_to_string(Arc::new(|val| {
+			}).with_smoother(SmoothingStyle::Linear(SMOOTHING_MS))
+			.with_value_to_string(Arc::new(|val| {
 				format!("{:.2}", val)
 			}))
 		}
 
+		let legacy_window_size_moved = Arc::new(AtomicBool::new(false));
+
 		Self {
-			editor_state: EguiState::from_size(540, 270),
+			editor_state: EguiState::from_size(EDITOR_WIDTH, EDITOR_HEIGHT),
 			a: default_daw_value("a"),
 			b: default_daw_value("b"),
 			c: default_daw_value("c"),
 			d: default_daw_value("d"),
+			scale_root: IntParam::new("scale_root", 0, IntRange::Linear {
+				min: 0, 
+				max: 11 
+			}).with_value_to_string(Arc::new(|val| {
+				String::from(NOTE_NAMES[val as usize])
+			})),
+			// a bit for each semitone above the root, see `scale`
+			scale: IntParam::new("scale", MAJOR as i32, IntRange::Linear {
+				min: 0, 
+				max: scale::CHROMATIC as i32 
+			}).with_value_to_string(Arc::new(|val| {
+				String::from(scale::scale_name(val as u16).unwrap_or("custom"))
+			})),
 
-			gain: FloatParam::new("gain", 1.0, FloatRange::Linear{ 
+			// still a linear gain up to 4 (+12 dB) so saved projects load at the same level, but
+			// skewed so the slider moves evenly in dB and 0 dB sits near the top rather than a quarter up
+			gain: FloatParam::new("gain", 1.0, FloatRange::Skewed{ 
 				min: 0.0, 
-				max: 4.0 
-			}).with_value_to_string(Arc::new(|val| {
+				max: 4.0,
+				factor: FloatRange::gain_skew_factor(-60.0, 12.0),
+			}).with_smoother(SmoothingStyle::Linear(SMOOTHING_MS))
+			.with_value_to_string(Arc::new(|val| {
 				let val = val as f64;
 				if val.log10().is_nan() {
 					String::from("-inf dB")
 				}else {
 					format!("{:.2} dB", 20.0 * val.log10())
 				}
+			}))
+			.with_string_to_value(formatters::s2v_f32_gain_to_db()),
+			bypass: BoolParam::new("bypass", false).make_bypass(),
+			width: FloatParam::new("width", 1.0, FloatRange::Linear{ 
+				min: 0.0, 
+				max: 2.0 
+			}).with_smoother(SmoothingStyle::Linear(SMOOTHING_MS))
+			.with_value_to_string(Arc::new(|val| {
+				format!("{:.0}%", val * 100.0)
+			})),
+			decorrelation: FloatParam::new("decorrelation", 0.0, FloatRange::Linear{ 
+				min: 0.0, 
+				max: 1.0 
+			}).with_value_to_string(Arc::new(|val| {
+				if val <= 0.0 {
+					String::from("off")
+				}else {
+					format!("{:.0}%", val * 100.0)
+				}
+			})),
+			ms_mode: BoolParam::new("ms_mode", false),
+			limiter: BoolParam::new("limiter", false),
+			// a hair under full scale at most, so rounding can't light the clip indicator
+			limiter_threshold: FloatParam::new("limiter_threshold", -1.0, FloatRange::Linear{ 
+				min: -24.0, 
+				max: -0.1 
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.1} dB", val)
+			})),
+			limiter_release: FloatParam::new("limiter_release", 100.0, FloatRange::Skewed{ 
+				min: 1.0, 
+				max: 1000.0,
+				factor: FloatRange::skew_factor(-2.0),
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.0} ms", val)
+			})),
+			dc_block: BoolParam::new("dc_block", false),
+			dc_cutoff: FloatParam::new("dc_cutoff", 10.0, FloatRange::Skewed{ 
+				min: 1.0, 
+				max: 200.0,
+				factor: FloatRange::skew_factor(-2.0),
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.1} Hz", val)
+			})),
+			freeze: BoolParam::new("freeze", false),
+			// 64 is the sustain pedal
+			freeze_cc: IntParam::new("freeze_cc", 64, IntRange::Linear {
+				min: 0, 
+				max: 127 
+			}).with_value_to_string(Arc::new(|val| {
+				format!("CC {}", val)
+			})),
+			morph: FloatParam::new("morph", 0.0, FloatRange::Linear{ 
+				min: 0.0, 
+				max: 1.0 
+			}).with_value_to_string(Arc::new(|val| {
+				match val {
+					val if val <= 0.0 => String::from("A"),
+					val if val >= 1.0 => String::from("B"),
+					val => format!("{:.0}%", val * 100.0),
+				}
+			})),
+			hold_time: FloatParam::new("hold_time", 0.0, FloatRange::Skewed{ 
+				min: 0.0, 
+				max: 30.0,
+				factor: FloatRange::skew_factor(-2.0),
+			}).with_value_to_string(Arc::new(|val| {
+				if val <= 0.0 {
+					String::from("off")
+				}else {
+					format!("{:.2} s", val)
+				}
+			})),
+			learn_noise: BoolParam::new("learn_noise", false),
+			// a multiple of the learned profile, past 100% to also take the noise above its average
+			denoise: FloatParam::new("denoise", 0.0, FloatRange::Linear{ 
+				min: 0.0, 
+				max: 4.0 
+			}).with_value_to_string(Arc::new(|val| {
+				if val == 0.0 {
+					String::from("off")
+				}else {
+					format!("{:.0}%", val * 100.0)
+				}
+			})),
+
+			pitch_shift: FloatParam::new("pitch_shift", 0.0, FloatRange::Linear{ 
+				min: -24.0, 
+				max: 24.0 
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.2} st", val)
+			})),
+			freq_shift: FloatParam::new("freq_shift", 0.0, FloatRange::SymmetricalSkewed{ 
+				min: -5000.0, 
+				max: 5000.0,
+				factor: FloatRange::skew_factor(-2.0),
+				center: 0.0,
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:+.1} Hz", val)
+			})),
+			preserve_formants: BoolParam::new("preserve_formants", false),
+			whisper: FloatParam::new("whisper", 0.0, FloatRange::Linear{ 
+				min: 0.0, 
+				max: 1.0 
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.2}", val)
+			})),
+			robotize: BoolParam::new("robotize", false),
+			smoothing: FloatParam::new("smoothing", 0.0, FloatRange::Linear{ 
+				min: 0.0, 
+				max: 0.99 
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.2}", val)
+			})),
+			tilt: FloatParam::new("tilt", 0.0, FloatRange::Linear{ 
+				min: -12.0, 
+				max: 12.0 
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.2} dB/oct", val)
+			})),
+			tilt_pivot: FloatParam::new("tilt_pivot", 1000.0, FloatRange::Skewed{ 
+				min: 20.0, 
+				max: 20000.0,
+				factor: FloatRange::skew_factor(-2.0),
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.0} Hz", val)
+			})),
+			slew: FloatParam::new("slew", 0.0, FloatRange::Skewed{ 
+				min: 0.0, 
+				max: 20000.0,
+				factor: FloatRange::skew_factor(-2.0),
+			}).with_value_to_string(Arc::new(|val| {
+				if val <= 0.0 {
+					String::from("off")
+				}else {
+					format!("{:.0} Hz/s", val)
+				}
+			})),
+			blur: FloatParam::new("blur", 0.0, FloatRange::Skewed{ 
+				min: 0.0, 
+				max: 1000.0,
+				factor: FloatRange::skew_factor(-2.0),
+			}).with_value_to_string(Arc::new(|val| {
+				if val <= 0.0 {
+					String::from("off")
+				}else {
+					format!("{:.0} Hz", val)
+				}
+			})),
+			// extra bins either side a mapped bin reaches, past the two it's split between at 0
+			spread: FloatParam::new("spread", 0.0, FloatRange::Linear{ 
+				min: 0.0, 
+				max: 4.0 
+			}).with_value_to_string(Arc::new(|val| {
+				if val <= 0.0 {
+					String::from("off")
+				}else {
+					format!("{:.1} bins", val)
+				}
+			})),
+			stretch: FloatParam::new("stretch", 1.0, FloatRange::Skewed{ 
+				min: 1.0, 
+				max: MAX_STRETCH,
+				factor: FloatRange::skew_factor(-1.0),
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.2}x", val)
+			})),
+			transient_sensitivity: FloatParam::new("transient_sensitivity", 0.0, FloatRange::Linear{ 
+				min: 0.0, 
+				max: 1.0 
+			}).with_value_to_string(Arc::new(|val| {
+				if val <= 0.0 {
+					String::from("off")
+				}else {
+					format!("{:.2}", val)
+				}
+			})),
+			// how the scripts' `sustain` follows each bin
+			sustain_attack: FloatParam::new("sustain_attack", 100.0, FloatRange::Skewed{ 
+				min: 0.0, 
+				max: 1000.0,
+				factor: FloatRange::skew_factor(-1.0),
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.0} ms", val)
+			})),
+			sustain_release: FloatParam::new("sustain_release", 300.0, FloatRange::Skewed{ 
+				min: 0.0, 
+				max: 2000.0,
+				factor: FloatRange::skew_factor(-1.0),
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.0} ms", val)
+			})),
+			harmonics: IntParam::new("harmonics", 0, IntRange::Linear {
+				min: 0, 
+				max: 16 
+			}).with_value_to_string(Arc::new(|val| {
+				if val <= 0 {
+					String::from("off")
+				}else {
+					format!("{}", val)
+				}
+			})),
+			harmonic_rolloff: FloatParam::new("harmonic_rolloff", 6.0, FloatRange::Linear{ 
+				min: 0.0, 
+				max: 24.0 
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.1} dB/oct", val)
+			})),
+			spectral_threshold: FloatParam::new("spectral_threshold", -30.0, FloatRange::Linear{ 
+				min: -80.0, 
+				max: 0.0 
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.1} dB", val)
+			})),
+			spectral_ratio: FloatParam::new("spectral_ratio", 1.0, FloatRange::Skewed{ 
+				min: 1.0, 
+				max: 20.0,
+				factor: FloatRange::skew_factor(-2.0),
+			}).with_value_to_string(Arc::new(|val| {
+				if val <= 1.0 {
+					String::from("off")
+				}else {
+					format!("{:.1}:1", val)
+				}
+			})),
+			spectral_dynamics: EnumParam::new("spectral_dynamics", SpectralDynamics::Compress),
+			spectral_makeup: FloatParam::new("spectral_makeup", 0.0, FloatRange::Linear{ 
+				min: 0.0, 
+				max: 24.0 
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.1} dB", val)
 			})),
 
-			window_size: IntParam::new("window_size", 11, IntRange::Linear {
+			seed: IntParam::new("seed", 0, IntRange::Linear {
+				min: 0, 
+				max: 9999 
+			}),
+			magnitude_in_db: BoolParam::new("magnitude_in_db", false),
+			peak_interpolation: BoolParam::new("peak_interpolation", false),
+			phase_lock: BoolParam::new("phase_lock", false),
+			preserve_energy: BoolParam::new("preserve_energy", false),
+			auto_makeup: BoolParam::new("auto_makeup", false),
+			range_mode: EnumParam::new("range_mode", RangeMode::Drop),
+
+			window_size: IntParam::new("window_size", WINDOW_SIZE.ilog2() as i32, IntRange::Linear {
 				min: 7, 
-				max: 12 
+				max: MAX_WINDOW_SIZE.ilog2() as i32 
 			}).with_value_to_string(Arc::new(|val| {
 				format!("{}", 2_i32.pow(val as u32))
 			})),
-			window_offset: IntParam::new("window_offset", 0, IntRange::Linear {
+			legacy_window_size: IntParam::new("window_size (legacy)", WINDOW_SIZE.ilog2() as i32, IntRange::Linear {
+				min: 7, 
+				max: 12 
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{}", 2_i32.pow(val as u32))
+			})).with_callback({
+				let moved = legacy_window_size_moved.clone();
+				Arc::new(move |_| moved.store(true, Ordering::Relaxed))
+			}).hide(),
+			legacy_window_size_moved,
+			// the padding factor as a power of two, like window_size
+			zero_pad: IntParam::new("zero_pad", 0, IntRange::Linear {
 				min: 0, 
-				max: 4096 
-			}),
+				max: MAX_ZERO_PAD.ilog2() as i32 
+			}).with_value_to_string(Arc::new(|val| {
+				if val == 0 {
+					String::from("off")
+				}else {
+					format!("{}x", 2_i32.pow(val as u32))
+				}
+			})),
+			low_latency: BoolParam::new("low_latency", false),
+			// a fraction of the window rather than samples, so it means the same at every window size,
+			// and smoothed so that automating it glides instead of stepping the window between frames
+			window_offset: FloatParam::new("window_offset", 0.0, FloatRange::Linear{ 
+				min: 0.0, 
+				max: 1.0 
+			}).with_smoother(SmoothingStyle::Linear(SMOOTHING_MS))
+			.with_value_to_string(Arc::new(|val| {
+				format!("{:.1}%", val * 100.0)
+			})),
 
 			window_factor: FloatParam::new("window_factor", 0.5, FloatRange::Linear{ 
 				min: 0.0, 
@@ -155,13 +722,176 @@ impl Default for Arguments {
 			}).with_value_to_string(Arc::new(|val| {
 				format!("{:.2}", val)
 			})),
+			script_window: BoolParam::new("script_window", false),
+			crossover_low: FloatParam::new("crossover_low", 200.0, FloatRange::Skewed{ 
+				min: 20.0, 
+				max: 20000.0,
+				factor: FloatRange::skew_factor(-2.0),
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.0} Hz", val)
+			})),
+			crossover_high: FloatParam::new("crossover_high", 2000.0, FloatRange::Skewed{ 
+				min: 20.0, 
+				max: 20000.0,
+				factor: FloatRange::skew_factor(-2.0),
+			}).with_value_to_string(Arc::new(|val| {
+				format!("{:.0} Hz", val)
+			})),
+			min_freq: FloatParam::new("min_freq", 0.0, FloatRange::Skewed{ 
+				min: 0.0, 
+				max: MAX_PROCESSED_FREQ,
+				factor: FloatRange::skew_factor(-2.0),
+			}).with_value_to_string(Arc::new(|val| {
+				if val <= 0.0 {
+					String::from("off")
+				}else {
+					format!("{:.0} Hz", val)
+				}
+			})),
+			max_freq: FloatParam::new("max_freq", MAX_PROCESSED_FREQ, FloatRange::Skewed{ 
+				min: 20.0, 
+				max: MAX_PROCESSED_FREQ,
+				factor: FloatRange::skew_factor(-2.0),
+			}).with_value_to_string(Arc::new(|val| {
+				if val >= MAX_PROCESSED_FREQ {
+					String::from("off")
+				}else {
+					format!("{:.0} Hz", val)
+				}
+			})),
+			out_of_band: EnumParam::new("out_of_band", OutOfBand::Pass),
 
 			map_code: RwLock::new(Ok(String::new())),
+			right_map_code: RwLock::new(Ok(String::new())),
+			low_band_code: RwLock::new(Ok(String::new())),
+			mid_band_code: RwLock::new(Ok(String::new())),
+			high_band_code: RwLock::new(Ok(String::new())),
 			date: Default::default(),
+			ab_slots: Default::default(),
+			osc: Default::default(),
+			midi_learn: Default::default(),
+			panic: AtomicBool::new(false),
+			take_snapshot: Default::default(),
+			snapshot_taken: Default::default(),
+			script_error: RwLock::new(None),
+			language: Default::default(),
 		}
 	}
 }
 
+/// Saves the CC bindings, which are kept in atomics for the audio thread rather than in a lock.
+impl<'a> PersistentField<'a, CcBindings> for MidiLearn {
+	fn set(&self, new_value: CcBindings) {
+		self.set_bindings(new_value);
+	}
+
+	fn map<F: Fn(&CcBindings) -> R, R>(&self, f: F) -> R {
+		f(&self.bindings())
+	}
+}
+
+/// Written by hand because the vocoder doesn't depend on nih_plug, so the enum can't derive it there.
+impl Enum for RangeMode {
+	fn variants() -> &'static [&'static str] {
+		&["Drop", "Clamp", "Wrap"]
+	}
+
+	fn ids() -> Option<&'static [&'static str]> {
+		Some(&["drop", "clamp", "wrap"])
+	}
+
+	fn to_index(self) -> usize {
+		self as usize
+	}
+
+	fn from_index(index: usize) -> Self {
+		RangeMode::ALL[index.min(RangeMode::ALL.len() - 1)]
+	}
+}
+
+impl Enum for OutOfBand {
+	fn variants() -> &'static [&'static str] {
+		&["Pass", "Mute"]
+	}
+
+	fn ids() -> Option<&'static [&'static str]> {
+		Some(&["pass", "mute"])
+	}
+
+	fn to_index(self) -> usize {
+		self as usize
+	}
+
+	fn from_index(index: usize) -> Self {
+		OutOfBand::ALL[index.min(OutOfBand::ALL.len() - 1)]
+	}
+}
+
+impl Enum for SpectralDynamics {
+	fn variants() -> &'static [&'static str] {
+		&["Compress", "Expand"]
+	}
+
+	fn ids() -> Option<&'static [&'static str]> {
+		Some(&["compress", "expand"])
+	}
+
+	fn to_index(self) -> usize {
+		self as usize
+	}
+
+	fn from_index(index: usize) -> Self {
+		SpectralDynamics::ALL[index.min(SpectralDynamics::ALL.len() - 1)]
+	}
+}
+
+/// Which script an edit from the editor goes to.
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum ScriptSlot {
+	/// Every channel's, unless the right channel has its own.
+	#[default] Main,
+	Right,
+	/// The band's, from low to high, shared by every channel.
+	Band(usize),
+}
+
+impl Arguments {
+	/// The window size in samples. The legacy `window_size` param takes over from the current one
+	/// once it has moved at all, which only automation recorded against it does, and keeps it from
+	/// then on, so automation stepping back onto its default still plays back.
+	pub fn window_samples(&self) -> usize {
+		let param = if self.legacy_window_size_moved.load(Ordering::Relaxed) { &self.legacy_window_size } else { &self.window_size };
+		2_usize.pow(param.value() as u32)
+	}
+
+	/// The macros `a` to `d`, in the order MIDI learn numbers them.
+	pub fn macros(&self) -> [&FloatParam; LEARNABLE] {
+		[&self.a, &self.b, &self.c, &self.d]
+	}
+
+	pub fn script(&self, slot: ScriptSlot) -> &RwLock<Result<String, String>> {
+		match slot {
+			ScriptSlot::Main => &self.map_code,
+			ScriptSlot::Right => &self.right_map_code,
+			ScriptSlot::Band(band) => [&self.low_band_code, &self.mid_band_code, &self.high_band_code][band],
+		}
+	}
+}
+
+impl Interface {
+	/// Lets the learned CC drive macro `index` from now on, gliding to `value`, 0 to 1 across the
+	/// param's range, from wherever the macro is.
+	fn take_over(&mut self, index: usize, value: f32, sample_rate: f32) {
+		let param = self.params.macros()[index];
+		let takeover = self.cc_takeovers[index].get_or_insert_with(|| {
+			let smoother = Smoother::new(SmoothingStyle::Linear(SMOOTHING_MS));
+			smoother.reset(param.smoothed.previous_value());
+			CcTakeover { param_value: param.value(), smoother }
+		});
+		takeover.smoother.set_target(sample_rate, param.preview_plain(value));
+	}
+}
+
 impl Plugin for Interface {
 	const NAME: &'static str = "I Am Freq Remapper";
 	const VENDOR: &'static str = "iamplugins";
@@ -172,95 +902,503 @@ impl Plugin for Interface {
 		AudioIOLayout {
 			main_input_channels: NonZeroU32::new(2),
 			main_output_channels: NonZeroU32::new(2),
+			aux_input_ports: &[new_nonzero_u32(2)],
+			names: PortNames {
+				aux_inputs: &["Sidechain"],
+				..PortNames::const_default()
+			},
 			..AudioIOLayout::const_default()
 		},
 		AudioIOLayout {
 			main_input_channels: NonZeroU32::new(1),
 			main_output_channels: NonZeroU32::new(1),
+			aux_input_ports: &[new_nonzero_u32(1)],
+			names: PortNames {
+				aux_inputs: &["Sidechain"],
+				..PortNames::const_default()
+			},
 			..AudioIOLayout::const_default()
 		},
 	];
 
+	const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+
 	type SysExMessage = ();
 	type BackgroundTask = ();
 
-	fn initialize(&mut self, _: &AudioIOLayout, config: &BufferConfig, ctx: &mut impl InitContext<Self>) -> bool {
+	fn initialize(&mut self, layout: &AudioIOLayout, config: &BufferConfig, ctx: &mut impl InitContext<Self>) -> bool {
 		let sample_rate = config.sample_rate;
-		self.processor[0] = Some(PhaseVocoder::new(WINDOW_SIZE, sample_rate));
-		self.processor[1] = Some(PhaseVocoder::new(WINDOW_SIZE, sample_rate));
-		ctx.set_latency_samples(WINDOW_SIZE as u32);
+		// one processor per channel, so the mono layout never touches a second one
+		let channel_count = layout.main_output_channels.map_or(0, NonZeroU32::get) as usize;
+		// built the way the project was saved, so it doesn't start with a fade over to its own
+		// settings and the latency reported here is already the one it runs at
+		let window_size = self.params.window_samples();
+		let zero_pad_factor = 2_usize.pow(self.params.zero_pad.value() as u32);
+		let low_latency = self.params.low_latency.value();
+		self.processor = (0..channel_count).map(|_| {
+			let mut processor = PhaseVocoder::new(window_size, sample_rate);
+			processor.renew_zero_pad(zero_pad_factor);
+			processor.renew_low_latency(low_latency);
+			processor
+		}).collect();
+		if let Some(processor) = self.processor.first_mut() {
+			processor.set_spectrum(self.spectrum.clone());
+			processor.set_capture(self.spectrum.capture_publisher());
+		}
+		self.dc_blockers = (0..channel_count).map(|_| DcBlocker::default()).collect();
+		let latency = self.processor.first().map_or_else(|| latency_for(window_size, OVERLAP_RATIO, low_latency), PhaseVocoder::latency);
+		ctx.set_latency_samples(latency as u32);
 		true
 	}
 
+	fn reset(&mut self) {
+		for processor in &mut self.processor {
+			processor.reset();
+		}
+		self.notes.clear();
+		self.freeze_pedal = false;
+		self.limiter.reset();
+		for dc_blocker in &mut self.dc_blockers {
+			dc_blocker.reset();
+		}
+	}
+
 	fn params(&self) -> Arc<dyn Params> {
 		self.params.clone()
 	}
 
-	fn process(&mut self, buf: &mut Buffer<'_>, _: &mut AuxiliaryBuffers<'_>, ctx: &mut impl ProcessContext<Self>) -> ProcessStatus {
-		let mut map_code = self.params.map_code.write().unwrap();
-		let mut result = Ok(());
+	// a project saved before `window_size` moved to its own ID loads its size into the new param,
+	// and leaves the legacy one at its default so it doesn't take over.
+	// `window_offset` used to be saved in samples, it becomes the same offset as a fraction of that window
+	fn filter_state(state: &mut PluginState) {
+		let default = ParamValue::I32(WINDOW_SIZE.ilog2() as i32);
+		if !state.params.contains_key("window_size_log2") && let Some(old) = state.params.insert(String::from("window_size"), default) {
+			state.params.insert(String::from("window_size_log2"), old);
+		}
 
-		if let Ok(code) = &*map_code {
-			for processor in &mut self.processor {
-				if let Some(processor) = processor { result = processor.update_mapping(code) }
-				if result.is_err() {
-					break;
+		if let Some(&ParamValue::I32(samples)) = state.params.get("window_offset") {
+			let window_size = match state.params.get("window_size_log2") {
+				Some(&ParamValue::I32(exponent)) => 1_i32 << exponent.clamp(7, MAX_WINDOW_SIZE.ilog2() as i32),
+				_ => WINDOW_SIZE as i32,
+			};
+			let offset = samples.rem_euclid(window_size) as f32 / window_size as f32;
+			state.params.insert(String::from("window_offset"), ParamValue::F32(offset));
+		}
+	}
+
+	fn process(&mut self, buf: &mut Buffer<'_>, aux: &mut AuxiliaryBuffers<'_>, ctx: &mut impl ProcessContext<Self>) -> ProcessStatus {
+		let started = Instant::now();
+		let panic = self.params.panic.swap(false, Ordering::Relaxed);
+		for slot in MorphSlot::ALL {
+			if self.params.take_snapshot[slot as usize].swap(false, Ordering::Relaxed) {
+				for processor in &mut self.processor {
+					processor.capture_snapshot(slot);
 				}
 			}
 		}
+		let mut map_code = self.params.map_code.write().unwrap();
+		let mut right_map_code = self.params.right_map_code.write().unwrap();
 
-		if let Err(e) = result {
-			*map_code = Err(e); 
+		// the second channel runs its own script once there is one, errors included, so a
+		// broken right script keeps that channel on its last good mapping
+		let right_has_own = !matches!(&*right_map_code, Ok(code) if code.is_empty());
+		for (i, processor) in self.processor.iter_mut().enumerate() {
+			let code = if i == 1 && right_has_own {
+				&mut *right_map_code
+			}else {
+				&mut *map_code
+			};
+			if let Ok(source) = &*code && let Err(e) = processor.update_mapping(source) {
+				*code = Err(e);
+			}
+		}
+		for band in 0..BANDS {
+			let mut code = self.params.script(ScriptSlot::Band(band)).write().unwrap();
+			for processor in self.processor.iter_mut() {
+				if let Ok(source) = &*code && let Err(e) = processor.update_band_mapping(band, source) {
+					*code = Err(e);
+				}
+			}
 		}
 
-		let daw_values = [
-			self.params.a.value(),
-			self.params.b.value(),
-			self.params.c.value(),
-			self.params.d.value(),
-		];
-
-		let gain = self.params.gain.value();
-		let window_size = 2_usize.pow(self.params.window_size.value() as u32);
+		let bypass = self.params.bypass.value();
+		let freeze_param = self.params.freeze.value();
+		let limiter = self.params.limiter.value();
+		let limiter_threshold = self.params.limiter_threshold.value();
+		let limiter_release = self.params.limiter_release.value();
+		let dc_block = self.params.dc_block.value();
+		let dc_cutoff = self.params.dc_cutoff.value();
+		let freeze_cc = self.params.freeze_cc.value() as u8;
+		let morph = self.params.morph.value();
+		let hold_time = self.params.hold_time.value();
+		let learn_noise = self.params.learn_noise.value();
+		let denoise = self.params.denoise.value();
+		let pitch_shift = self.params.pitch_shift.value();
+		let freq_shift = self.params.freq_shift.value();
+		let preserve_formants = self.params.preserve_formants.value();
+		let seed = self.params.seed.value() as u64;
+		let whisper = self.params.whisper.value();
+		let decorrelation = self.params.decorrelation.value();
+		let robotize = self.params.robotize.value();
+		let smoothing = self.params.smoothing.value();
+		let tilt = self.params.tilt.value();
+		let tilt_pivot = self.params.tilt_pivot.value();
+		let slew = self.params.slew.value();
+		let blur = self.params.blur.value();
+		let spread = self.params.spread.value();
+		let stretch = self.params.stretch.value();
+		let transient_sensitivity = self.params.transient_sensitivity.value();
+		let sustain_attack = self.params.sustain_attack.value();
+		let sustain_release = self.params.sustain_release.value();
+		let harmonics = self.params.harmonics.value() as usize;
+		let harmonic_rolloff = self.params.harmonic_rolloff.value();
+		let spectral_threshold = self.params.spectral_threshold.value();
+		let spectral_ratio = self.params.spectral_ratio.value();
+		let spectral_dynamics = self.params.spectral_dynamics.value();
+		let spectral_makeup = self.params.spectral_makeup.value();
+		let magnitude_in_db = self.params.magnitude_in_db.value();
+		let peak_interpolation = self.params.peak_interpolation.value();
+		let phase_lock = self.params.phase_lock.value();
+		let preserve_energy = self.params.preserve_energy.value();
+		let auto_makeup = self.params.auto_makeup.value();
+		let range_mode = self.params.range_mode.value();
+		let window_size = self.params.window_samples();
+		let zero_pad_factor = 2_usize.pow(self.params.zero_pad.value() as u32);
+		let low_latency = self.params.low_latency.value();
 		let window_factor = self.params.window_factor.value();
-		let window_offset = self.params.window_offset.value() as usize % window_size;
-
-		ctx.set_latency_samples(window_size as u32);
+		let script_window = self.params.script_window.value();
+		let crossovers = [self.params.crossover_low.value(), self.params.crossover_high.value()];
+		let min_freq = self.params.min_freq.value();
+		// the top of the slider means no limit, so bins above 20 kHz aren't left out at higher sample rates
+		let max_freq = match self.params.max_freq.value() {
+			max_freq if max_freq >= MAX_PROCESSED_FREQ => 0.0,
+			max_freq => max_freq,
+		};
+		let out_of_band = self.params.out_of_band.value();
+		let scale_root = self.params.scale_root.value() as usize;
+		let scale = self.params.scale.value() as u16;
 
 		let transport = ctx.transport();
 		let bpm = transport.tempo.unwrap_or(0.0) as f32;
 		let sample_rate = transport.sample_rate;
 		let daw_time = transport.pos_seconds().unwrap_or(0.0) as f32;
+		let playing = transport.playing;
+		let looping = transport.loop_range_beats().is_some();
+		let beat = transport.pos_beats().unwrap_or(0.0) as f32;
+		let bar_start = transport.bar_start_pos_beats().unwrap_or(0.0) as f32;
+		let beats_per_bar = transport.time_sig_numerator.unwrap_or(4) as f32 * 4.0 
+			/ transport.time_sig_denominator.unwrap_or(4) as f32;
 		let sys_time = (OffsetDateTime::now_utc() - self.instant).as_seconds_f32();
 
-		for (i, samples) in buf.as_slice().iter_mut().enumerate() {
-			let processor = &mut self.processor[i % 2];
-
-			let input_params = InputParams {
-				daw_values,
-				// sustain_values: self.sustain_values[i % 2].read().map(|inner| *inner).unwrap_or_default(),
-				current_track_id: i % 2,
-				bpm,
-				daw_time,
-				sys_time,
-				window_factor,
-				window_offset,
-				window_size,
-				gain,
-				sample_rate,
+		let meters = &*self.meters;
+		meters.set_channel_count(buf.channels());
+		// a mono layout has no side to split off
+		let mid_side = self.params.ms_mode.value() && buf.channels() == 2;
+
+		// `a`..`d` and `gain` are smoothed, so they're sampled once per hop instead of once per
+		// buffer; every frame then sees values at most one hop old
+		let hop = (window_size / OVERLAP_RATIO).max(1);
+		let len = buf.samples();
+		let channels = buf.as_slice();
+		let channel_count = channels.len();
+		let sidechain_channels = aux.inputs.first().map_or(&[][..], |input| input.as_slice_immutable());
+		let mut start = 0;
+		let mut next_event = ctx.next_event();
+		// the most the limiter took off during this buffer, in dB
+		let mut reduction = 0.0_f32;
+
+		while start < len {
+			let end = (start + hop).min(len);
+			let steps = (end - start) as u32;
+
+			let gain = self.params.gain.smoothed.next_step(steps);
+			let width = self.params.width.smoothed.next_step(steps);
+			let window_offset = self.params.window_offset.smoothed.next_step(steps);
+
+			// hosts that leave the sidechain unconnected either pass silence or no buffer at all,
+			// and both read as 0
+			let sidechain = {
+				let sum = sidechain_channels.iter()
+					.filter_map(|samples| samples.get(start..end))
+					.flatten()
+					.fold(0.0, |sum, sample| sum + sample * sample);
+				let count = sidechain_channels.len() * (end - start);
+				if count > 0 { (sum / count as f32).sqrt() } else { 0.0 }
+			};
+
+			// the playhead only moves while playing
+			let elapsed = if playing { start as f32 / sample_rate } else { 0.0 };
+			let beat = beat + elapsed * bpm / 60.0;
+			let bar_phase = if beats_per_bar > 0.0 { ((beat - bar_start) / beats_per_bar).rem_euclid(1.0) } else { 0.0 };
+
+			// notes are applied at the start of the hop they fall in, like the smoothed params
+			while let Some(event) = next_event && (event.timing() as usize) < end {
+				match event {
+					NoteEvent::NoteOn { note, velocity, .. } => self.notes.note_on(note, velocity),
+					NoteEvent::NoteOff { note, .. } => self.notes.note_off(note),
+					// pedals send 0 to 127, down from the halfway point
+					NoteEvent::MidiCC { cc, value, .. } if cc == freeze_cc => self.freeze_pedal = value >= 0.5,
+					NoteEvent::MidiCC { cc, value, .. } => {
+						if let Some(index) = self.params.midi_learn.handle_cc(cc) && value.is_finite() {
+							self.take_over(index, value.clamp(0.0, 1.0), sample_rate);
+						}
+					},
+					_ => (),
+				}
+				next_event = ctx.next_event();
+			}
+
+			// after the CCs, so one moves its macro from the hop it falls in. Moving the param, or
+			// forgetting the CC, has the param glide back from wherever the CC left the macro
+			let macros = self.params.macros();
+			let daw_values: [f32; LEARNABLE] = std::array::from_fn(|index| {
+				let param = macros[index];
+				let takeover = &mut self.cc_takeovers[index];
+				let handed_back = takeover.as_ref().is_some_and(|cc| {
+					cc.param_value != param.value() || self.params.midi_learn.binding(index).is_none()
+				});
+				if handed_back && let Some(cc) = takeover.take() {
+					param.smoothed.reset(cc.smoother.previous_value());
+					param.smoothed.set_target(sample_rate, param.value());
+				}
+				let value = param.smoothed.next_step(steps);
+				takeover.as_ref().map_or(value, |cc| cc.smoother.next_step(steps))
+			});
+			let (note_freq, note_vel) = self.notes.current();
+			let freeze = freeze_param || self.freeze_pedal;
+
+			let input_params = |i: usize| {
+				InputParams {
+					daw_values,
+					sustain_attack,
+					sustain_release,
+					current_track_id: i,
+					channel_count,
+					mid_side,
+					bpm,
+					playing,
+					looping,
+					beat,
+					bar_phase,
+					daw_time: daw_time + elapsed,
+					sys_time,
+					window_factor,
+					window_offset,
+					window_size,
+					zero_pad_factor,
+					low_latency,
+					crossovers,
+					min_freq,
+					max_freq,
+					out_of_band,
+					gain,
+					sample_rate,
+					pitch_shift,
+					freq_shift,
+					preserve_formants,
+					seed,
+					whisper,
+					decorrelation,
+					robotize,
+					magnitude_in_db,
+					peak_interpolation,
+					phase_lock,
+					smoothing,
+					tilt,
+					tilt_pivot,
+					script_window,
+					slew,
+					stretch,
+					sidechain,
+					bypass,
+					transient_sensitivity,
+					note_freq,
+					note_vel,
+					scale_root,
+					scale,
+					harmonics,
+					harmonic_rolloff,
+					freeze,
+					morph,
+					hold_time,
+					learn_noise,
+					denoise,
+					blur,
+					spread,
+					preserve_energy,
+					auto_makeup,
+					range_mode,
+					spectral_threshold,
+					spectral_ratio,
+					spectral_dynamics,
+					spectral_makeup,
+				}
 			};
-			
-			if let Some(processor) = processor {
-				processor.process(samples, &input_params);
+
+			// metered as left and right even when the vocoders get mid and side
+			for (i, samples) in channels.iter().enumerate() {
+				if let Some(meter) = meters.channel(i) {
+					meter.input.update(&samples[start..end], sample_rate);
+				}
+			}
+			if mid_side && let [left, right] = channels {
+				for (l, r) in left[start..end].iter_mut().zip(&mut right[start..end]) {
+					(*l, *r) = ((*l + *r) * 0.5, (*l - *r) * 0.5);
+				}
+			}
+
+			let process_channel = |i: usize, samples: &mut [f32], processor: &mut PhaseVocoder| {
+				processor.process(&mut samples[start..end], &input_params(i));
 			};
+			let bypass_before = self.processor.first().map_or(0.0, |processor| processor.bypass_mix());
+
+			cfg_if::cfg_if! {
+				if #[cfg(feature = "parallel")] {
+					use rayon::prelude::*;
+					// rayon boxes its jobs, which is the only allocation on this path
+					nih_plug::util::permit_alloc(|| {
+						channels
+							.par_iter_mut()
+							.zip(self.processor.par_iter_mut())
+							.enumerate()
+							.for_each(|(i, (samples, processor))| process_channel(i, samples, processor));
+					});
+				}else {
+					for (i, (samples, processor)) in channels.iter_mut().zip(self.processor.iter_mut()).enumerate() {
+						process_channel(i, samples, processor);
+					}
+				}
+			}
 
+			// back to left and right before anything else sees the pair; the bypassed, latency-matched
+			// input went through the vocoders as mid and side as well, so it decodes to itself
+			if mid_side && let [left, right] = channels {
+				for (m, s) in left[start..end].iter_mut().zip(&mut right[start..end]) {
+					(*m, *s) = (*m + *s, *m - *s);
+				}
+			}
+
+			// `width` scales the side signal of the processed pair. It fades out along with the
+			// vocoder's own bypass crossfade, so bypassed audio comes through untouched
+			let bypass_after = self.processor.first().map_or(0.0, |processor| processor.bypass_mix());
+			if let [left, right] = channels {
+				let block_len = (end - start) as f32;
+				for (i, (l, r)) in left[start..end].iter_mut().zip(&mut right[start..end]).enumerate() {
+					let mix = bypass_before + (bypass_after - bypass_before) * (i + 1) as f32 / block_len;
+					let width = 1.0 + (width - 1.0) * (1.0 - mix);
+					if width != 1.0 {
+						let mid = (*l + *r) * 0.5;
+						let side = (*l - *r) * 0.5 * width;
+						*l = mid + side;
+						*r = mid - side;
+					}
+				}
+			}
+
+			// before the limiter, so an offset doesn't eat into its headroom; like `width` it fades
+			// out with the bypass crossfade
+			if dc_block {
+				let block_len = (end - start) as f32;
+				for (samples, dc_blocker) in channels.iter_mut().zip(&mut self.dc_blockers) {
+					for (i, sample) in samples[start..end].iter_mut().enumerate() {
+						let filtered = dc_blocker.next(*sample, dc_cutoff, sample_rate);
+						let mix = bypass_before + (bypass_after - bypass_before) * (i + 1) as f32 / block_len;
+						*sample = filtered + (*sample - filtered) * mix;
+					}
+				}
+			}else {
+				// so turning it on starts from rest instead of from whatever it last saw
+				for dc_blocker in &mut self.dc_blockers {
+					dc_blocker.reset();
+				}
+			}
+
+			// last, so nothing after it can push the output back over; like `width` it fades out with
+			// the bypass crossfade
+			if limiter {
+				let block_len = (end - start) as f32;
+				for i in start..end {
+					let peak = channels.iter().fold(0.0_f32, |peak, samples| peak.max(samples[i].abs()));
+					let gain = self.limiter.next(peak, limiter_threshold, limiter_release, sample_rate);
+					let mix = bypass_before + (bypass_after - bypass_before) * (i - start + 1) as f32 / block_len;
+					let gain = gain + (1.0 - gain) * mix;
+					for samples in channels.iter_mut() {
+						samples[i] *= gain;
+					}
+					reduction = reduction.max(self.limiter.reduction());
+				}
+			}
+
+			for (i, samples) in channels.iter().enumerate() {
+				let output_peak = meters.channel(i).map_or(0.0, |meter| meter.output.update(&samples[start..end], sample_rate));
+				if output_peak > 1.0 {
+					meters.clip();
+				}
+			}
+
+			start = end;
+		}
+
+		// what the vocoders actually run at, which only follows a new window size or latency mode
+		// once they've faded over to it
+		if let Some(processor) = self.processor.first() {
+			ctx.set_latency_samples(processor.latency() as u32);
+			for slot in MorphSlot::ALL {
+				self.params.snapshot_taken[slot as usize].store(processor.has_snapshot(slot), Ordering::Relaxed);
+			}
+		}
+
+		// only copied when it changes, so a script that keeps failing doesn't allocate every block;
+		// if the editor holds the lock, the next block gets it
+		let script_error = self.processor.iter().find_map(|processor| processor.script_error());
+		if let Ok(mut error) = self.params.script_error.try_write() && error.as_deref() != script_error {
+			*error = script_error.map(String::from);
+		}
+
+		meters.set_gain_reduction(reduction);
+		meters.update_load(started.elapsed().as_secs_f32(), len as f32 / sample_rate);
+
+		// the block already on its way out fades to nothing, so the only thing heard is the
+		// silence while the vocoder fills up again
+		if panic {
+			for samples in channels.iter_mut() {
+				for (i, sample) in samples.iter_mut().enumerate() {
+					*sample *= 1.0 - (i + 1) as f32 / len as f32;
+				}
+			}
+			self.reset();
+		}
+
+		// lets the host stop calling once the input has been silent for a window, and for as long as
+		// a hold takes to fall from full scale to silence; a freeze or the lagging stretch keeps
+		// sounding for as long as it's on
+		if freeze_param || self.freeze_pedal || stretch > 1.0 {
+			ProcessStatus::KeepAlive
+		}else {
+			let hold_tail = hold_time * DB_FLOOR / -60.0 * sample_rate;
+			ProcessStatus::Tail(window_size as u32 + hold_tail as u32)
 		}
-		ProcessStatus::Normal
 	}
 
 	fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
 		let params = self.params.clone();
-		create_egui_editor(params.editor_state.clone(), GuiInfo::default(), |_ctx, _| {
+		let spectrum = self.spectrum.clone();
+		let meters = self.meters.clone();
+
+		#[allow(unused_mut)]
+		let ab_slots = params.ab_slots.read().unwrap().clone();
+		let mut gui_info = GuiInfo {
+			presets: preset::list(),
+			persist_ab: ab_slots.is_some(),
+			ab: ab_slots.unwrap_or_default(),
+			..Default::default()
+		};
+		#[cfg(all(feature = "en_us", feature = "zh_cn"))]
+		{
+			gui_info.language = *params.language.read().unwrap();
+		}
+
+		create_egui_editor(params.editor_state.clone(), gui_info, |_ctx, _| {
 			#[cfg(feature = "zh_cn_support")]
 			{
 				let mut fonts = egui::FontDefinitions::default();
@@ -274,7 +1412,11 @@ impl Plugin for Interface {
 				_ctx.set_fonts(fonts);
 			}
 		}, move |ctx, setter, state| {
-			egui::CentralPanel::default().show(ctx, |ui| {
+			// the size the window is dragged to is kept in `editor_state`, which is persisted
+			// with the project, so it's restored the next time the editor opens
+			ResizableWindow::new("editor")
+			.min_size(Vec2::new(EDITOR_WIDTH as f32, EDITOR_HEIGHT as f32))
+			.show(ctx, params.editor_state.as_ref(), |ui| {
 				cfg_if::cfg_if! {
 					if #[cfg(all(feature = "en_us", feature = "zh_cn"))] {
 							egui::TopBottomPanel::top("language settings").show(ctx, |ui| {
@@ -283,20 +1425,625 @@ impl Plugin for Interface {
 									ui.selectable_value(&mut state.language, Language::ZhCn, "中文");
 									ui.selectable_value(&mut state.language, Language::EnUs, "English");
 								});
+								if *params.language.read().unwrap() != state.language {
+									*params.language.write().unwrap() = state.language;
+								}
 								ui.allocate_space(Vec2::new(ui.available_width(), 4.0));
 							});
 							match state.language {
-								Language::ZhCn => zh_cn_ui(ui, setter, &params, state),
-								Language::EnUs => en_us_ui(ui, setter, &params, state),
+								Language::ZhCn => zh_cn_ui(ui, setter, &params, state, &spectrum, &meters),
+								Language::EnUs => en_us_ui(ui, setter, &params, state, &spectrum, &meters),
 							}
 					}else if #[cfg(feature = "zh_cn")] {
-						zh_cn_ui(ui, setter, &params, state);
+						zh_cn_ui(ui, setter, &params, state, &spectrum, &meters);
 					}else if #[cfg(feature = "en_us")] {
-						en_us_ui(ui, setter, &params, state);
+						en_us_ui(ui, setter, &params, state, &spectrum, &meters);
 					}
 				}
 			});
-		})
+		})
+	}
+}
+
+/// Draws the input (gray) and output (highlighted) magnitude spectra published by the first channel.
+fn spectrum_ui(ui: &mut egui::Ui, spectrum: &SpectrumData, state: &GuiInfo) {
+	let (response, painter) = ui.allocate_painter(Vec2::new(ui.available_width(), SPECTRUM_HEIGHT), egui::Sense::hover());
+	let rect = response.rect;
+	painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+	ui.ctx().request_repaint();
+
+	let bins = spectrum.bins();
+	let nyquist = spectrum.sample_rate() / 2.0;
+	if bins < 2 || nyquist <= SPECTRUM_MIN_FREQUENCY {
+		return;
+	}
+
+	let to_pos = |k: usize, magnitude: f32| {
+		let frequency = k as f32 / (bins - 1) as f32 * nyquist;
+		let x = if state.linear_frequency {
+			frequency / nyquist
+		}else {
+			(frequency.max(SPECTRUM_MIN_FREQUENCY) / SPECTRUM_MIN_FREQUENCY).ln() / (nyquist / SPECTRUM_MIN_FREQUENCY).ln()
+		};
+		let y = if state.linear_magnitude {
+			magnitude
+		}else {
+			1.0 - 20.0 * magnitude.max(f32::MIN_POSITIVE).log10() / SPECTRUM_MIN_DB
+		};
+		rect.lerp_inside(Vec2::new(x.clamp(0.0, 1.0), 1.0 - y.clamp(0.0, 1.0)))
+	};
+
+	let input = (1..bins).map(|k| to_pos(k, spectrum.input(k))).collect::<Vec<_>>();
+	let output = (1..bins).map(|k| to_pos(k, spectrum.output(k))).collect::<Vec<_>>();
+	painter.add(egui::Shape::line(input, egui::Stroke::new(1.0, egui::Color32::GRAY)));
+	painter.add(egui::Shape::line(output, egui::Stroke::new(1.0, ui.visuals().selection.stroke.color)));
+}
+
+/// Draws one level as a bar from `METER_MIN_DB` to 0 dBFS: the filled part is the RMS,
+/// the tick is the held peak.
+/// Shows the latency caused by the current window size and latency mode, plus a warning once it
+/// gets long enough to be noticeable while playing live.
+fn latency_ui(ui: &mut egui::Ui, params: &Arguments, spectrum: &SpectrumData, warning: &str) {
+	let sample_rate = spectrum.sample_rate();
+	if sample_rate <= 0.0 {
+		return;
+	}
+
+	let window_size = params.window_samples();
+	let latency = latency_for(window_size, OVERLAP_RATIO, params.low_latency.value()) as f32 / sample_rate * 1000.0;
+	ui.label(format!("{:.1} ms", latency));
+	if latency > LATENCY_WARNING_MS {
+		ui.colored_label(egui::Color32::YELLOW, warning);
+	}
+}
+
+/// The factor the overlap-add is divided back down by for the window the params pick, with a
+/// warning if that window doesn't add up to a constant at the plugin's overlap, and a button that
+/// moves `window_factor` to the nearest one that does. `labels` are the warning, the button and
+/// its hover text.
+fn overlap_add_ui(ui: &mut egui::Ui, setter: &ParamSetter<'_>, params: &Arguments, labels: [&str; 3]) {
+	let window_size = params.window_samples();
+	let offset = params.window_offset.value();
+	let ola = overlap_add(window_size, OVERLAP_RATIO, offset, params.window_factor.value());
+	ui.label(format!("OLA ×{:.2}", 1.0 / ola.gain));
+	if ola.ripple > OVERLAP_RIPPLE_WARNING {
+		ui.colored_label(egui::Color32::YELLOW, labels[0]);
+	}
+	if ui.button(labels[1]).on_hover_text(labels[2]).clicked() {
+		let factor = flattest_window_factor(window_size, OVERLAP_RATIO, offset, params.window_factor.value());
+		setter.begin_set_parameter(&params.window_factor);
+		setter.set_parameter(&params.window_factor, factor);
+		setter.end_set_parameter(&params.window_factor);
+	}
+}
+
+/// The share of real time spent in `process`, as a small bar and a percentage.
+fn load_ui(ui: &mut egui::Ui, meters: &Meters) {
+	let load = meters.load();
+	let color = if load > 1.0 {
+		egui::Color32::RED
+	}else if load > LOAD_WARNING {
+		egui::Color32::YELLOW
+	}else {
+		egui::Color32::DARK_GREEN
+	};
+
+	let (response, painter) = ui.allocate_painter(Vec2::new(LOAD_METER_WIDTH, METER_HEIGHT), egui::Sense::hover());
+	let rect = response.rect;
+	let right = rect.lerp_inside(Vec2::new(load.clamp(0.0, 1.0), 0.0)).x;
+	painter.rect_filled(rect, 1.0, ui.visuals().extreme_bg_color);
+	painter.rect_filled(egui::Rect::from_x_y_ranges(rect.left()..=right, rect.y_range()), 1.0, color);
+	ui.label(format!("CPU {:.0}%", load * 100.0));
+}
+
+/// How much the output limiter is taking off, as a bar growing from the right and the figure in dB.
+fn gain_reduction_ui(ui: &mut egui::Ui, meters: &Meters) {
+	let reduction = meters.gain_reduction();
+	let (response, painter) = ui.allocate_painter(Vec2::new(LOAD_METER_WIDTH, METER_HEIGHT), egui::Sense::hover());
+	let rect = response.rect;
+	let left = rect.lerp_inside(Vec2::new(1.0 - (reduction / GAIN_REDUCTION_RANGE_DB).clamp(0.0, 1.0), 0.0)).x;
+	painter.rect_filled(rect, 1.0, ui.visuals().extreme_bg_color);
+	painter.rect_filled(egui::Rect::from_x_y_ranges(left..=rect.right(), rect.y_range()), 1.0, egui::Color32::YELLOW);
+	ui.label(format!("-{:.1} dB", reduction));
+}
+
+fn level_ui(ui: &mut egui::Ui, level: &Level) {
+	let (response, painter) = ui.allocate_painter(Vec2::new(METER_WIDTH, METER_HEIGHT), egui::Sense::hover());
+	let rect = response.rect;
+	let to_x = |gain: f32| {
+		let db = 20.0 * gain.max(f32::MIN_POSITIVE).log10();
+		rect.lerp_inside(Vec2::new((1.0 - db / METER_MIN_DB).clamp(0.0, 1.0), 0.0)).x
+	};
+
+	let peak = level.peak();
+	let peak_color = if peak > 1.0 { egui::Color32::RED } else { egui::Color32::LIGHT_GRAY };
+	painter.rect_filled(rect, 1.0, ui.visuals().extreme_bg_color);
+	painter.rect_filled(egui::Rect::from_x_y_ranges(rect.left()..=to_x(level.rms()), rect.y_range()), 1.0, egui::Color32::DARK_GREEN);
+	painter.vline(to_x(peak), rect.y_range(), egui::Stroke::new(1.0, peak_color));
+}
+
+/// Writes the next frame's input and output spectra to a CSV file, for bug reports and for tuning
+/// scripts. `labels` are the button, its tooltip, what's shown while waiting for the frame and
+/// what's shown before where the file went.
+fn export_ui(ui: &mut egui::Ui, params: &Arguments, spectrum: &SpectrumData, state: &mut GuiInfo, labels: [&str; 4]) {
+	let [button, hint, waiting, saved] = labels;
+	if ui.add_enabled(state.spectrum_export.is_none(), egui::Button::new(button)).on_hover_text(hint).clicked() {
+		spectrum.request_capture();
+		state.export_status = String::from(waiting);
+	}
+
+	if let Some(capture) = spectrum.take_capture() {
+		state.spectrum_export = Some(export::SpectrumExport::start(capture, export_header(params)));
+	}
+	if let Some(export) = &state.spectrum_export {
+		match export.take() {
+			Some(Ok(path)) => {
+				state.export_status = format!("{saved} {}", path.to_string_lossy());
+				state.spectrum_export = None;
+			}
+			Some(Err(e)) => {
+				state.export_status = e;
+				state.spectrum_export = None;
+			}
+			None => ui.ctx().request_repaint_after(std::time::Duration::from_millis(50)),
+		}
+	}
+	if !state.export_status.is_empty() {
+		ui.label(&state.export_status);
+	}
+}
+
+/// What an export records about how its frame was made: every param as the editor shows it, and
+/// a hash of each script.
+fn export_header(params: &Arguments) -> Vec<(String, String)> {
+	let mut header = vec![(String::from("version"), String::from(Interface::VERSION))];
+	for (id, ptr, _) in params.param_map() {
+		// SAFETY: the pointers come from `params`, which outlives this call
+		let value = unsafe { ptr.normalized_value_to_string(ptr.modulated_normalized_value(), true) };
+		header.push((id, value));
+	}
+
+	let scripts = [
+		("map_code", ScriptSlot::Main),
+		("right_map_code", ScriptSlot::Right),
+		("low_band_code", ScriptSlot::Band(0)),
+		("mid_band_code", ScriptSlot::Band(1)),
+		("high_band_code", ScriptSlot::Band(2)),
+	];
+	for (name, slot) in scripts {
+		let hash = match &*params.script(slot).read().unwrap() {
+			Ok(code) if code.is_empty() => String::from("empty"),
+			Ok(code) => format!("{:016x}", export::script_hash(code)),
+			Err(_) => String::from("error"),
+		};
+		header.push((format!("{name}_hash"), hash));
+	}
+	header
+}
+
+/// Draws the input and output meters of every active channel plus the clip indicator,
+/// which stays lit until it is clicked. `labels` are the input, output and clip captions.
+fn meters_ui(ui: &mut egui::Ui, meters: &Meters, labels: [&str; 3]) {
+	ui.horizontal(|ui| {
+		ui.label(labels[0]);
+		for channel in meters.active_channels() {
+			level_ui(ui, &channel.input);
+		}
+		ui.label(labels[1]);
+		for channel in meters.active_channels() {
+			level_ui(ui, &channel.output);
+		}
+
+		let clip = if meters.clipped() {
+			egui::RichText::new(labels[2]).color(egui::Color32::RED)
+		}else {
+			egui::RichText::new(labels[2]).weak()
+		};
+		if ui.button(clip).clicked() {
+			meters.clear_clip();
+		}
+	});
+}
+
+/// Save/load of complete patches. `labels` are the title, the placeholder shown when no
+/// preset is picked, and the save, load and refresh buttons.
+fn preset_ui(ui: &mut egui::Ui, setter: &ParamSetter<'_>, params: &Arguments, state: &mut GuiInfo, labels: [&str; 5]) {
+	let [title, placeholder, save, load, refresh] = labels;
+	ui.label(title);
+	ui.separator();
+	ui.horizontal(|ui| {
+		ui.text_edit_singleline(&mut state.preset_name);
+		if ui.button(save).clicked() {
+			state.preset_status = match preset::save(&state.preset_name, &Preset::capture(params)) {
+				Ok(()) => {
+					state.presets = preset::list();
+					String::new()
+				},
+				Err(e) => e,
+			};
+		}
+	});
+	ui.horizontal(|ui| {
+		let selected = if state.preset_name.is_empty() { placeholder } else { state.preset_name.as_str() };
+		egui::ComboBox::from_id_salt("presets")
+			.selected_text(selected.to_string())
+			.show_ui(ui, |ui| {
+				for name in &state.presets {
+					ui.selectable_value(&mut state.preset_name, name.clone(), name);
+				}
+			});
+		if ui.button(load).clicked() {
+			state.preset_status = match preset::load(&state.preset_name) {
+				Ok(preset) => {
+					preset.apply(params, setter);
+					String::new()
+				},
+				Err(e) => e,
+			};
+		}
+		if ui.button(refresh).clicked() {
+			state.presets = preset::list();
+		}
+	});
+	if !state.preset_status.is_empty() {
+		ui.label(&state.preset_status);
+	}
+	ui.allocate_space(Vec2::new(0.0, 8.0));
+}
+
+/// A/B comparison. `labels` are the title, the copy button and the checkbox that keeps the
+/// slots with the project instead of only while the editor is open.
+fn ab_ui(ui: &mut egui::Ui, setter: &ParamSetter<'_>, params: &Arguments, state: &mut GuiInfo, labels: [&str; 3]) {
+	let [title, copy, persist] = labels;
+	let mut changed = false;
+	ui.horizontal(|ui| {
+		ui.label(title);
+		for (slot, name) in ["A", "B"].into_iter().enumerate() {
+			if ui.selectable_label(state.ab.active == slot, name).clicked() {
+				state.ab.switch(slot, params, setter);
+				changed = true;
+			}
+		}
+		if ui.button(copy).clicked() {
+			state.ab.copy_a_to_b(params, setter);
+			changed = true;
+		}
+		changed |= ui.checkbox(&mut state.persist_ab, persist).changed();
+	});
+
+	if changed {
+		*params.ab_slots.write().unwrap() = state.persist_ab.then(|| state.ab.clone());
+	}
+	ui.allocate_space(Vec2::new(0.0, 8.0));
+}
+
+/// OSC remote control of `a`..`d`. `labels` are the title, the enable checkbox and the port.
+/// The listener is (re)started whenever the settings change, and what it received is handed
+/// to the host here, on the GUI thread, the same way a slider drag would be.
+#[cfg(feature = "osc")]
+fn osc_ui(ui: &mut egui::Ui, setter: &ParamSetter<'_>, params: &Arguments, state: &mut GuiInfo, labels: [&str; 3]) {
+	let [title, enable, port] = labels;
+	let mut settings = *params.osc.read().unwrap();
+	ui.horizontal(|ui| {
+		ui.label(title);
+		ui.checkbox(&mut settings.enabled, enable);
+		ui.label(port);
+		ui.add(egui::DragValue::new(&mut settings.port).range(1024..=65535));
+	});
+	if settings != *params.osc.read().unwrap() {
+		*params.osc.write().unwrap() = settings;
+	}
+
+	let wanted = settings.enabled.then_some(settings.port);
+	if wanted != state.osc_port {
+		// the old listener has to let go of its port before a new one can bind it
+		state.osc = None;
+		state.osc_status.clear();
+		if let Some(port) = wanted {
+			match osc::OscServer::start(port) {
+				Ok(server) => state.osc = Some(server),
+				Err(e) => state.osc_status = e,
+			}
+		}
+		state.osc_port = wanted;
+	}
+
+	if let Some(server) = &state.osc {
+		let macros = [&params.a, &params.b, &params.c, &params.d];
+		for (index, value) in server.take() {
+			setter.begin_set_parameter(macros[index]);
+			setter.set_parameter(macros[index], value);
+			setter.end_set_parameter(macros[index]);
+		}
+		// egui only redraws on input, so keep polling while listening
+		ui.ctx().request_repaint_after(std::time::Duration::from_millis(20));
+	}
+	if !state.osc_status.is_empty() {
+		ui.label(&state.osc_status);
+	}
+	ui.allocate_space(Vec2::new(0.0, 8.0));
+}
+
+/// The MIDI learn button for macro `index`. `labels` are the button before anything is learned,
+/// while waiting for a CC, and its hover text. The learned CC itself is applied by `process`, so
+/// it works with the editor closed, and doesn't move the slider.
+fn midi_learn_ui(ui: &mut egui::Ui, params: &Arguments, index: usize, labels: [&str; 3]) {
+	let [learn, waiting, hint] = labels;
+	let midi_learn = &params.midi_learn;
+	let text = match (midi_learn.learning() == Some(index), midi_learn.binding(index)) {
+		(true, _) => String::from(waiting),
+		(false, Some(cc)) => format!("CC {cc}"),
+		(false, None) => String::from(learn),
+	};
+	let response = ui.selectable_label(midi_learn.learning() == Some(index), text).on_hover_text(hint);
+	if response.clicked() {
+		midi_learn.learn(index);
+	}
+	if response.secondary_clicked() {
+		midi_learn.forget(index);
+	}
+
+	// egui only redraws on input, so keep polling until the CC shows up
+	if midi_learn.learning() == Some(index) {
+		ui.ctx().request_repaint_after(std::time::Duration::from_millis(20));
+	}
+}
+
+/// How far the first channel's mapping transposes, in semitones and cents, or `non_uniform` when
+/// it moves different pitches by different amounts. Only shown once `pitch_shift`, `freq_shift` or
+/// a script could move anything, and worked out by the editor's own processor.
+fn tuning_ui(ui: &mut egui::Ui, params: &Arguments, spectrum: &SpectrumData, state: &mut GuiInfo, non_uniform: &str) {
+	let sample_rate = spectrum.sample_rate();
+	if sample_rate <= 0.0 {
+		return;
+	}
+
+	let main = params.script(ScriptSlot::Main).read().unwrap().clone();
+	let bands: [_; BANDS] = std::array::from_fn(|band| params.script(ScriptSlot::Band(band)).read().unwrap().clone());
+	let scripted = std::iter::once(&main).chain(&bands).any(|code| !matches!(code, Ok(code) if code.is_empty()));
+	if params.pitch_shift.value() == 0.0 && params.freq_shift.value() == 0.0 && !scripted {
+		return;
+	}
+
+	let input_params = InputParams {
+		daw_values: [params.a.value(), params.b.value(), params.c.value(), params.d.value()],
+		window_size: params.window_samples(),
+		zero_pad_factor: 2_usize.pow(params.zero_pad.value() as u32),
+		crossovers: [params.crossover_low.value(), params.crossover_high.value()],
+		min_freq: params.min_freq.value(),
+		max_freq: match params.max_freq.value() {
+			max_freq if max_freq >= MAX_PROCESSED_FREQ => 0.0,
+			max_freq => max_freq,
+		},
+		out_of_band: params.out_of_band.value(),
+		sample_rate,
+		pitch_shift: params.pitch_shift.value(),
+		freq_shift: params.freq_shift.value(),
+		seed: params.seed.value() as u64,
+		magnitude_in_db: params.magnitude_in_db.value(),
+		scale_root: params.scale_root.value() as usize,
+		scale: params.scale.value() as u16,
+		range_mode: params.range_mode.value(),
+		..Default::default()
+	};
+	let transposition = state.tuning.transposition(&input_params, main.as_deref().ok(), bands.each_ref().map(|code| code.as_deref().ok()));
+	match transposition {
+		Transposition::Uniform(cents) => ui.label(tuning::format_cents(cents)),
+		Transposition::NonUniform => ui.label(non_uniform),
+	};
+}
+
+/// Fetches a script from a web address into the selected slot. `labels` are the address hint and the
+/// button. The script goes through the history like Load does, so a bad download can be undone.
+#[cfg(feature = "url")]
+fn url_ui(ui: &mut egui::Ui, params: &Arguments, state: &mut GuiInfo, labels: [&str; 2]) {
+	let [hint, button] = labels;
+	ui.horizontal(|ui| {
+		ui.add(egui::TextEdit::singleline(&mut state.script_url).hint_text(hint));
+		if ui.add_enabled(state.download.is_none(), egui::Button::new(button)).clicked() {
+			state.download = Some((state.script_slot, download::ScriptDownload::start(&state.script_url)));
+			state.download_status.clear();
+		}
+		if state.download.is_some() {
+			ui.spinner();
+		}
+	});
+
+	if let Some((slot, download)) = &state.download {
+		match download.take() {
+			Some(Ok(code)) => {
+				state.history.replace(params, *slot, Ok(code));
+				state.download = None;
+			}
+			Some(Err(e)) => {
+				state.download_status = e;
+				state.download = None;
+			}
+			// egui only redraws on input, so keep polling until it's done
+			None => ui.ctx().request_repaint_after(std::time::Duration::from_millis(50)),
+		}
+	}
+	if !state.download_status.is_empty() {
+		ui.label(&state.download_status);
+	}
+}
+
+/// Loads a `.rhai` file dropped onto the editor the same way the Load button does, and dims
+/// the window with `hint` while one is being dragged over it. Other files are ignored.
+fn script_drop_ui(ctx: &egui::Context, params: &Arguments, history: &mut CodeHistory, slot: ScriptSlot, hint: &str) {
+	let (hovering, dropped) = ctx.input(|i| (!i.raw.hovered_files.is_empty(), i.raw.dropped_files.clone()));
+
+	if hovering {
+		let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("script_drop")));
+		let rect = ctx.screen_rect();
+		painter.rect_filled(rect, 0.0, egui::Color32::from_black_alpha(192));
+		painter.text(rect.center(), egui::Align2::CENTER_CENTER, hint, egui::TextStyle::Heading.resolve(&ctx.style()), egui::Color32::WHITE);
+	}
+
+	let is_script = |file: &egui::DroppedFile| match &file.path {
+		Some(path) => path.extension().is_some_and(|extension| extension == "rhai"),
+		None => file.name.ends_with(".rhai"),
+	};
+	if let Some(file) = dropped.iter().find(|file| is_script(file)) {
+		let code = match (&file.path, &file.bytes) {
+			(Some(path), _) => std::fs::read_to_string(path).map_err(|err| format!("{}", err)),
+			(None, Some(bytes)) => String::from_utf8(bytes.to_vec()).map_err(|err| format!("{}", err)),
+			(None, None) => Err(format!("can't read `{}`", file.name)),
+		};
+		history.replace(params, slot, code);
+	}
+}
+
+/// Dropdown that loads one of the bundled [`EXAMPLES`] into `slot` like the Load button would.
+/// `names` label the examples in the same order.
+fn examples_ui(ui: &mut egui::Ui, params: &Arguments, history: &mut CodeHistory, slot: ScriptSlot, title: &str, names: [&str; 5]) {
+	egui::ComboBox::from_id_salt("examples")
+		.selected_text(title)
+		.show_ui(ui, |ui| {
+			for (name, code) in names.into_iter().zip(EXAMPLES) {
+				if ui.selectable_label(false, name).clicked() {
+					history.replace(params, slot, Ok(code.to_string()));
+				}
+			}
+		});
+}
+
+/// A collapsible list of every variable and function a script can use, with `describe` picking
+/// the description in the editor's language. `labels` are the header and the two section titles.
+fn reference_ui(ui: &mut egui::Ui, labels: [&str; 3], describe: fn(&ApiEntry) -> &'static str) {
+	egui::CollapsingHeader::new(labels[0]).id_salt("reference").show(ui, |ui| {
+		for (title, entries) in [(labels[1], &script_api::VARIABLES[..]), (labels[2], &script_api::FUNCTIONS[..])] {
+			ui.label(title);
+			egui::Grid::new(title).striped(true).show(ui, |ui| {
+				for entry in entries {
+					ui.monospace(entry.name);
+					ui.monospace(entry.kind);
+					ui.label(describe(entry));
+					ui.end_row();
+				}
+			});
+		}
+	});
+}
+
+/// A one-octave keyboard for `scale` and `scale_root`: clicking a key puts it in the scale or
+/// takes it out, right-clicking makes it the root, which moves the whole scale along with it.
+/// `hint` explains that on hover.
+fn scale_ui(ui: &mut egui::Ui, setter: &ParamSetter<'_>, params: &Arguments, hint: &str) {
+	// where the black keys sit, in white keys from the left edge
+	const WHITE_KEYS: [usize; 7] = [0, 2, 4, 5, 7, 9, 11];
+	const BLACK_KEYS: [(usize, f32); 5] = [(1, 1.0), (3, 2.0), (6, 4.0), (8, 5.0), (10, 6.0)];
+
+	let root = params.scale_root.value() as usize;
+	let scale = params.scale.value() as u16;
+	let (response, painter) = ui.allocate_painter(Vec2::new(7.0 * KEY_WIDTH, KEY_HEIGHT), egui::Sense::click());
+	let rect = response.rect;
+	let white_rect = |index: usize| egui::Rect::from_min_size(
+		rect.left_top() + Vec2::new(index as f32 * KEY_WIDTH, 0.0),
+		Vec2::new(KEY_WIDTH, KEY_HEIGHT)
+	);
+	let black_rect = |position: f32| egui::Rect::from_center_size(
+		egui::pos2(rect.left() + position * KEY_WIDTH, rect.top() + KEY_HEIGHT * 0.3),
+		Vec2::new(KEY_WIDTH * 0.6, KEY_HEIGHT * 0.6)
+	);
+	let keys = WHITE_KEYS.iter().enumerate().map(|(index, &note)| (note, white_rect(index)))
+		.chain(BLACK_KEYS.iter().map(|&(note, position)| (note, black_rect(position))));
+
+	let selected = ui.visuals().selection.bg_fill;
+	for (note, key) in keys.clone() {
+		let fill = if scale::contains(scale, root, note) {
+			selected
+		}else if WHITE_KEYS.contains(&note) {
+			egui::Color32::from_gray(220)
+		}else {
+			egui::Color32::from_gray(40)
+		};
+		painter.rect_filled(key.shrink(0.5), 1.0, fill);
+		if note == root {
+			painter.circle_filled(egui::pos2(key.center().x, key.bottom() - 6.0), 3.0, egui::Color32::RED);
+		}
+	}
+
+	// black keys are drawn over the white ones, so they're hit first
+	let clicked = |pos: egui::Pos2| keys.clone().rev().find(|(_, key)| key.contains(pos)).map(|(note, _)| note);
+	let response = response.on_hover_text(hint);
+	if let Some(note) = response.interact_pointer_pos().and_then(clicked) {
+		if response.clicked() {
+			setter.begin_set_parameter(&params.scale);
+			setter.set_parameter(&params.scale, scale::toggle(scale, root, note) as i32);
+			setter.end_set_parameter(&params.scale);
+		}else if response.secondary_clicked() {
+			setter.begin_set_parameter(&params.scale_root);
+			setter.set_parameter(&params.scale_root, note as i32);
+			setter.end_set_parameter(&params.scale_root);
+		}
+	}
+	ui.label(format!("{} {}", NOTE_NAMES[root], scale::scale_name(scale).unwrap_or("")));
+}
+
+/// Learn Noise, which holds `learn_noise` on for as long as it's pressed. Only a press or release
+/// here sets the param, so it doesn't fight the host's automation of it the rest of the time.
+fn learn_noise_ui(ui: &mut egui::Ui, setter: &ParamSetter<'_>, params: &Arguments, state: &mut GuiInfo, label: &str) {
+	let learning = params.learn_noise.value();
+	let held = ui.add(egui::Button::new(label).selected(learning)).is_pointer_button_down_on();
+	if held != state.learning_noise {
+		state.learning_noise = held;
+		setter.begin_set_parameter(&params.learn_noise);
+		setter.set_parameter(&params.learn_noise, held);
+		setter.end_set_parameter(&params.learn_noise);
+	}
+}
+
+/// The A and B buttons next to `morph`, lit once the processors hold that snapshot. A click takes
+/// the next frame as it; `hints` are their hover texts.
+fn snapshot_ui(ui: &mut egui::Ui, params: &Arguments, hints: [&str; 2]) {
+	for (slot, hint) in MorphSlot::ALL.into_iter().zip(hints) {
+		let name = match slot {
+			MorphSlot::A => "A",
+			MorphSlot::B => "B",
+		};
+		let taken = params.snapshot_taken[slot as usize].load(Ordering::Relaxed);
+		if ui.selectable_label(taken, name).on_hover_text(hint).clicked() {
+			params.take_snapshot[slot as usize].store(true, Ordering::Relaxed);
+		}
+	}
+}
+
+/// Picks the script that Load, Clear, the examples and dropped files go to. `labels` are the two
+/// channel slots and the notes shown while the right channel's or a band's is picked, `bands`
+/// the band slots from low to high.
+fn script_slot_ui(ui: &mut egui::Ui, slot: &mut ScriptSlot, labels: [&str; 4], bands: [&str; BANDS]) {
+	let [main, right, right_note, band_note] = labels;
+	ui.horizontal(|ui| {
+		ui.selectable_value(slot, ScriptSlot::Main, main);
+		ui.selectable_value(slot, ScriptSlot::Right, right);
+		for (band, label) in bands.into_iter().enumerate() {
+			ui.selectable_value(slot, ScriptSlot::Band(band), label);
+		}
+	});
+	match slot {
+		ScriptSlot::Main => {},
+		ScriptSlot::Right => { ui.label(egui::RichText::new(right_note).weak()); },
+		ScriptSlot::Band(_) => { ui.label(egui::RichText::new(band_note).weak()); },
+	}
+}
+
+/// Undo/redo of script changes made from the editor, also bound to Ctrl+Z and Ctrl+Y
+/// (or Ctrl+Shift+Z). `labels` are the two buttons.
+fn history_ui(ui: &mut egui::Ui, params: &Arguments, history: &mut CodeHistory, labels: [&str; 2]) {
+	let [undo, redo] = labels;
+	let (undo_key, redo_key) = ui.ctx().input_mut(|i| {
+		// shift isn't checked by the plain shortcut, so the redo one has to be taken first
+		let redo_key = i.consume_key(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::Z)
+			|| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Y);
+		(i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z), redo_key)
+	});
+
+	if ui.add_enabled(history.can_undo(), egui::Button::new(undo)).clicked() || undo_key {
+		history.undo(params);
+	}
+	if ui.add_enabled(history.can_redo(), egui::Button::new(redo)).clicked() || redo_key {
+		history.redo(params);
 	}
 }
 
@@ -306,10 +2053,27 @@ fn en_us_ui(
 	setter: &ParamSetter<'_>, 
 	params: &Arc<Arguments>,
 	state: &mut GuiInfo,
+	spectrum: &SpectrumData,
+	meters: &Meters,
 ) {
+	script_drop_ui(ui.ctx(), params, &mut state.history, state.script_slot, "Drop to load the .rhai script");
 	egui::CentralPanel::default().show(ui.ctx(), |ui| {
+		egui::TopBottomPanel::bottom("spectrum").show_inside(ui, |ui| {
+			ui.horizontal(|ui| {
+				ui.label("Spectrum");
+				ui.checkbox(&mut state.linear_frequency, "Linear Frequency");
+				ui.checkbox(&mut state.linear_magnitude, "Linear Magnitude");
+				if ui.button("Panic").on_hover_text("Silence the output and start processing afresh").clicked() {
+					params.panic.store(true, Ordering::Relaxed);
+				}
+				export_ui(ui, params, spectrum, state, ["Export CSV", "Save the next frame's spectra and the params to a file", "Waiting for audio…", "Saved to"]);
+			});
+			spectrum_ui(ui, spectrum, state);
+			meters_ui(ui, meters, ["In", "Out", "Clip"]);
+		});
+
 		egui::SidePanel::right("right")
-		.max_width(260.0)
+		.max_width((ui.available_width() / 2.0).max(260.0))
 		.default_width(260.0)
 		.min_width(260.0)
 		.show_inside(ui, |ui| { egui::ScrollArea::both().show(ui, |ui| {
@@ -319,61 +2083,294 @@ fn en_us_ui(
 			ui.horizontal(|ui| {
 				ui.label("a");
 				ui.add(ParamSlider::for_param(&params.a, setter));
+				midi_learn_ui(ui, params, 0, ["Learn", "Move a knob…", "Click, then move a MIDI controller to drive this param; right-click to forget it"]);
 			});
 			ui.horizontal(|ui| {
 				ui.label("b");
 				ui.add(ParamSlider::for_param(&params.b, setter));
+				midi_learn_ui(ui, params, 1, ["Learn", "Move a knob…", "Click, then move a MIDI controller to drive this param; right-click to forget it"]);
 			});
 			ui.horizontal(|ui| {
 				ui.label("c");
 				ui.add(ParamSlider::for_param(&params.c, setter));
+				midi_learn_ui(ui, params, 2, ["Learn", "Move a knob…", "Click, then move a MIDI controller to drive this param; right-click to forget it"]);
 			});
 			ui.horizontal(|ui| {
 				ui.label("d");
 				ui.add(ParamSlider::for_param(&params.d, setter));
+				midi_learn_ui(ui, params, 3, ["Learn", "Move a knob…", "Click, then move a MIDI controller to drive this param; right-click to forget it"]);
+			});
+			ui.horizontal(|ui| {
+				ui.label("scale");
+				scale_ui(ui, setter, params, "Click a key to add or remove it, right-click to make it the root");
 			});
 			ui.horizontal(|ui| {
 				ui.label("out_gain");
 				ui.add(ParamSlider::for_param(&params.gain, setter));
 			});
+			ui.horizontal(|ui| {
+				ui.label("bypass");
+				ui.add(ParamSlider::for_param(&params.bypass, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("width");
+				ui.add(ParamSlider::for_param(&params.width, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("decorrelation");
+				ui.add(ParamSlider::for_param(&params.decorrelation, setter));
+				ui.label("widens mono, sums back to mono intact");
+			});
+			ui.horizontal(|ui| {
+				ui.label("ms_mode");
+				ui.add(ParamSlider::for_param(&params.ms_mode, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("limiter");
+				ui.add(ParamSlider::for_param(&params.limiter, setter));
+				gain_reduction_ui(ui, meters);
+			});
+			ui.horizontal(|ui| {
+				ui.label("limiter_threshold");
+				ui.add(ParamSlider::for_param(&params.limiter_threshold, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("limiter_release");
+				ui.add(ParamSlider::for_param(&params.limiter_release, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("dc_block");
+				ui.add(ParamSlider::for_param(&params.dc_block, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("dc_cutoff");
+				ui.add(ParamSlider::for_param(&params.dc_cutoff, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("freeze");
+				ui.add(ParamSlider::for_param(&params.freeze, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("freeze_cc");
+				ui.add(ParamSlider::for_param(&params.freeze_cc, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("morph");
+				ui.add(ParamSlider::for_param(&params.morph, setter));
+				snapshot_ui(ui, params, ["Take the next frame as A", "Take the next frame as B"]);
+			});
+			ui.horizontal(|ui| {
+				ui.label("hold_time");
+				ui.add(ParamSlider::for_param(&params.hold_time, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("denoise");
+				ui.add(ParamSlider::for_param(&params.denoise, setter));
+				learn_noise_ui(ui, setter, params, state, "Learn Noise");
+			});
+			ui.horizontal(|ui| {
+				ui.label("pitch_shift");
+				ui.add(ParamSlider::for_param(&params.pitch_shift, setter));
+				tuning_ui(ui, params, spectrum, state, "non-uniform");
+			});
+			ui.horizontal(|ui| {
+				ui.label("freq_shift");
+				ui.add(ParamSlider::for_param(&params.freq_shift, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("preserve_formants");
+				ui.add(ParamSlider::for_param(&params.preserve_formants, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("whisper");
+				ui.add(ParamSlider::for_param(&params.whisper, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("robotize");
+				ui.add(ParamSlider::for_param(&params.robotize, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("smoothing");
+				ui.add(ParamSlider::for_param(&params.smoothing, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("blur");
+				ui.add(ParamSlider::for_param(&params.blur, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("spread");
+				ui.add(ParamSlider::for_param(&params.spread, setter));
+				ui.label("keeps the level of bins mapped between two others");
+			});
+			ui.horizontal(|ui| {
+				ui.label("tilt");
+				ui.add(ParamSlider::for_param(&params.tilt, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("tilt_pivot");
+				ui.add(ParamSlider::for_param(&params.tilt_pivot, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("slew");
+				ui.add(ParamSlider::for_param(&params.slew, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("stretch");
+				ui.add(ParamSlider::for_param(&params.stretch, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("transients");
+				ui.add(ParamSlider::for_param(&params.transient_sensitivity, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("sustain_attack");
+				ui.add(ParamSlider::for_param(&params.sustain_attack, setter));
+				ui.label("how fast the scripts' `sustain` follows each bin");
+			});
+			ui.horizontal(|ui| {
+				ui.label("sustain_release");
+				ui.add(ParamSlider::for_param(&params.sustain_release, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("harmonics");
+				ui.add(ParamSlider::for_param(&params.harmonics, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("harmonic_rolloff");
+				ui.add(ParamSlider::for_param(&params.harmonic_rolloff, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("spectral_threshold");
+				ui.add(ParamSlider::for_param(&params.spectral_threshold, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("spectral_ratio");
+				ui.add(ParamSlider::for_param(&params.spectral_ratio, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("spectral_dynamics");
+				ui.add(ParamSlider::for_param(&params.spectral_dynamics, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("spectral_makeup");
+				ui.add(ParamSlider::for_param(&params.spectral_makeup, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("seed");
+				ui.add(ParamSlider::for_param(&params.seed, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("magnitude_in_db");
+				ui.add(ParamSlider::for_param(&params.magnitude_in_db, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("peak_interpolation");
+				ui.add(ParamSlider::for_param(&params.peak_interpolation, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("phase_lock");
+				ui.add(ParamSlider::for_param(&params.phase_lock, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("preserve_energy");
+				ui.add(ParamSlider::for_param(&params.preserve_energy, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("auto_makeup");
+				ui.add(ParamSlider::for_param(&params.auto_makeup, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("range_mode");
+				ui.add(ParamSlider::for_param(&params.range_mode, setter));
+			});
 			ui.horizontal(|ui| {
 				ui.label("window_size");
 				ui.add(ParamSlider::for_param(&params.window_size, setter));
+				latency_ui(ui, params, spectrum, "high latency and CPU usage");
+				load_ui(ui, meters);
+			});
+			ui.horizontal(|ui| {
+				ui.label("low_latency");
+				ui.add(ParamSlider::for_param(&params.low_latency, setter));
+				ui.label("half the latency, rougher when the spectrum changes");
+			});
+			ui.horizontal(|ui| {
+				ui.label("zero_pad");
+				ui.add(ParamSlider::for_param(&params.zero_pad, setter));
+				ui.label("finer bins, more CPU");
 			});
 			ui.horizontal(|ui| {
 				ui.label("window_factor");
 				ui.add(ParamSlider::for_param(&params.window_factor, setter));
+				overlap_add_ui(ui, setter, params, ["uneven overlap-add", "Auto", "Pick the nearest factor that reconstructs flat"]);
 			});
 			ui.horizontal(|ui| {
 				ui.label("window_offset");
 				ui.add(ParamSlider::for_param(&params.window_offset, setter));
 			});
+			ui.horizontal(|ui| {
+				ui.label("script_window");
+				ui.add(ParamSlider::for_param(&params.script_window, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("crossover_low");
+				ui.add(ParamSlider::for_param(&params.crossover_low, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("crossover_high");
+				ui.add(ParamSlider::for_param(&params.crossover_high, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("min_freq");
+				ui.add(ParamSlider::for_param(&params.min_freq, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("max_freq");
+				ui.add(ParamSlider::for_param(&params.max_freq, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("out_of_band");
+				ui.add(ParamSlider::for_param(&params.out_of_band, setter));
+				ui.label("bins outside min_freq to max_freq skip the scripts");
+			});
 		})});
 
 		egui::ScrollArea::both().show(ui, |ui| {
 			ui.allocate_space(Vec2::new(ui.available_width(), 3.0));
+			preset_ui(ui, setter, params, state, ["Presets", "(none)", "Save Preset", "Load Preset", "Refresh"]);
+			ab_ui(ui, setter, params, state, ["Compare", "Copy A→B", "Keep with project"]);
+			#[cfg(feature = "osc")]
+			osc_ui(ui, setter, params, state, ["OSC", "Listen", "Port"]);
 			ui.label("Mapper Pannel");
 			ui.separator();
 			ui.label(format!("Will read map script from `{}`", PATH_TO_READ.to_string_lossy()));
 			ui.allocate_space(Vec2::new(0.0, 1.0));
+			script_slot_ui(
+				ui, 
+				&mut state.script_slot, 
+				["All Channels", "Right Channel", "While empty, the right channel runs the main script", "While empty, the band runs the channel's script"],
+				["Low Band", "Mid Band", "High Band"]
+			);
 			ui.horizontal(|ui| {
 				if ui.button("Load").clicked() {
 					let path = &*PATH_TO_READ;
 
 					let code = std::fs::read_to_string(path).map_err(|err| format!("{}", err));
-					*params.map_code.write().unwrap() = code;
-					*params.date.write().unwrap() = OffsetDateTime::now_utc().to_string();
+					state.history.replace(params, state.script_slot, code);
 				}
 				if ui.button("Clear (Double Click)").double_clicked() {
-					*params.map_code.write().unwrap() = Ok(String::new());
-					*params.date.write().unwrap() = OffsetDateTime::now_utc().to_string();
+					state.history.replace(params, state.script_slot, Ok(String::new()));
 				}
 				if ui.button("Show Code").clicked() {
 					state.show_code = !state.show_code;
 				}
+				history_ui(ui, params, &mut state.history, ["Undo", "Redo"]);
+				examples_ui(ui, params, &mut state.history, state.script_slot, "Examples", ["Octave Up", "Frequency Shift", "Scale Snap", "Spectral Gate", "Chord Snap"]);
 			});
+			#[cfg(feature = "url")]
+			url_ui(ui, params, state, ["https://…/script.rhai", "Load from URL"]);
 			ui.allocate_space(Vec2::new(0.0, 1.0));
-			let code_info = params.map_code.read().unwrap();
+			let code_info = params.script(state.script_slot).read().unwrap();
 			match &*code_info {
 				Ok(inner) => {
 					if inner.is_empty() {
@@ -388,6 +2385,10 @@ fn en_us_ui(
 					ui.label(e);
 				}
 			}
+			if let Some(e) = &*params.script_error.read().unwrap() {
+				ui.colored_label(egui::Color32::YELLOW, "Failed while running, those bins were passed through:");
+				ui.label(e);
+			}
 
 			ui.allocate_space(Vec2::new(0.0, 8.0));
 			ui.label("Code");
@@ -410,6 +2411,9 @@ fn en_us_ui(
 			}else {
 				ui.label("collapsed");
 			}
+
+			ui.allocate_space(Vec2::new(0.0, 8.0));
+			reference_ui(ui, ["Script Reference", "Variables", "Functions"], |entry| entry.en_us);
 		});
 	});
 }
@@ -420,10 +2424,27 @@ fn zh_cn_ui(
 	setter: &ParamSetter<'_>, 
 	params: &Arc<Arguments>,
 	state: &mut GuiInfo,
+	spectrum: &SpectrumData,
+	meters: &Meters,
 ) {
+	script_drop_ui(ui.ctx(), params, &mut state.history, state.script_slot, "松开以加载 .rhai 脚本");
 	egui::CentralPanel::default().show(ui.ctx(), |ui| {
+		egui::TopBottomPanel::bottom("频谱").show_inside(ui, |ui| {
+			ui.horizontal(|ui| {
+				ui.label("频谱");
+				ui.checkbox(&mut state.linear_frequency, "线性频率");
+				ui.checkbox(&mut state.linear_magnitude, "线性振幅");
+				if ui.button("紧急静音").on_hover_text("立即静音输出并重新开始处理").clicked() {
+					params.panic.store(true, Ordering::Relaxed);
+				}
+				export_ui(ui, params, spectrum, state, ["导出 CSV", "把下一帧的频谱和全部参数保存到文件", "等待音频…", "已保存到"]);
+			});
+			spectrum_ui(ui, spectrum, state);
+			meters_ui(ui, meters, ["输入", "输出", "削波"]);
+		});
+
 		egui::SidePanel::right("侧边栏")
-		.max_width(270.0)
+		.max_width((ui.available_width() / 2.0).max(270.0))
 		.default_width(270.0)
 		.min_width(270.0)
 		.show_inside(ui, |ui| { egui::ScrollArea::both().show(ui, |ui| {
@@ -433,61 +2454,294 @@ fn zh_cn_ui(
 			ui.horizontal(|ui| {
 				ui.label("a");
 				ui.add(ParamSlider::for_param(&params.a, setter));
+				midi_learn_ui(ui, params, 0, ["学习", "请转动旋钮…", "点击后转动 MIDI 控制器即可用它控制这个参数，右键单击取消绑定"]);
 			});
 			ui.horizontal(|ui| {
 				ui.label("b");
 				ui.add(ParamSlider::for_param(&params.b, setter));
+				midi_learn_ui(ui, params, 1, ["学习", "请转动旋钮…", "点击后转动 MIDI 控制器即可用它控制这个参数，右键单击取消绑定"]);
 			});
 			ui.horizontal(|ui| {
 				ui.label("c");
 				ui.add(ParamSlider::for_param(&params.c, setter));
+				midi_learn_ui(ui, params, 2, ["学习", "请转动旋钮…", "点击后转动 MIDI 控制器即可用它控制这个参数，右键单击取消绑定"]);
 			});
 			ui.horizontal(|ui| {
 				ui.label("d");
 				ui.add(ParamSlider::for_param(&params.d, setter));
+				midi_learn_ui(ui, params, 3, ["学习", "请转动旋钮…", "点击后转动 MIDI 控制器即可用它控制这个参数，右键单击取消绑定"]);
+			});
+			ui.horizontal(|ui| {
+				ui.label("音阶");
+				scale_ui(ui, setter, params, "单击琴键加入或移出音阶，右键单击设为主音");
 			});
 			ui.horizontal(|ui| {
 				ui.label("输出增益");
 				ui.add(ParamSlider::for_param(&params.gain, setter));
 			});
+			ui.horizontal(|ui| {
+				ui.label("旁通");
+				ui.add(ParamSlider::for_param(&params.bypass, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("立体声宽度");
+				ui.add(ParamSlider::for_param(&params.width, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("去相关");
+				ui.add(ParamSlider::for_param(&params.decorrelation, setter));
+				ui.label("把单声道变宽，合并为单声道时不受影响");
+			});
+			ui.horizontal(|ui| {
+				ui.label("中/侧处理");
+				ui.add(ParamSlider::for_param(&params.ms_mode, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("限幅器");
+				ui.add(ParamSlider::for_param(&params.limiter, setter));
+				gain_reduction_ui(ui, meters);
+			});
+			ui.horizontal(|ui| {
+				ui.label("限幅阈值");
+				ui.add(ParamSlider::for_param(&params.limiter_threshold, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("限幅释放");
+				ui.add(ParamSlider::for_param(&params.limiter_release, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("去直流");
+				ui.add(ParamSlider::for_param(&params.dc_block, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("去直流截止频率");
+				ui.add(ParamSlider::for_param(&params.dc_cutoff, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("频谱冻结");
+				ui.add(ParamSlider::for_param(&params.freeze, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("冻结踏板 CC");
+				ui.add(ParamSlider::for_param(&params.freeze_cc, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("冻结变形");
+				ui.add(ParamSlider::for_param(&params.morph, setter));
+				snapshot_ui(ui, params, ["把下一帧取为 A", "把下一帧取为 B"]);
+			});
+			ui.horizontal(|ui| {
+				ui.label("峰值保持");
+				ui.add(ParamSlider::for_param(&params.hold_time, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("降噪");
+				ui.add(ParamSlider::for_param(&params.denoise, setter));
+				learn_noise_ui(ui, setter, params, state, "学习噪声");
+			});
+			ui.horizontal(|ui| {
+				ui.label("音高偏移");
+				ui.add(ParamSlider::for_param(&params.pitch_shift, setter));
+				tuning_ui(ui, params, spectrum, state, "非均匀");
+			});
+			ui.horizontal(|ui| {
+				ui.label("频率偏移");
+				ui.add(ParamSlider::for_param(&params.freq_shift, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("保留共振峰");
+				ui.add(ParamSlider::for_param(&params.preserve_formants, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("耳语");
+				ui.add(ParamSlider::for_param(&params.whisper, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("机器人声");
+				ui.add(ParamSlider::for_param(&params.robotize, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("频谱平滑");
+				ui.add(ParamSlider::for_param(&params.smoothing, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("频谱模糊");
+				ui.add(ParamSlider::for_param(&params.blur, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("插值宽度");
+				ui.add(ParamSlider::for_param(&params.spread, setter));
+				ui.label("映射到两个频点之间时保持音量");
+			});
+			ui.horizontal(|ui| {
+				ui.label("频谱倾斜");
+				ui.add(ParamSlider::for_param(&params.tilt, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("倾斜中心");
+				ui.add(ParamSlider::for_param(&params.tilt_pivot, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("滑音速度");
+				ui.add(ParamSlider::for_param(&params.slew, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("时间拉伸");
+				ui.add(ParamSlider::for_param(&params.stretch, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("瞬态保护");
+				ui.add(ParamSlider::for_param(&params.transient_sensitivity, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("包络起音");
+				ui.add(ParamSlider::for_param(&params.sustain_attack, setter));
+				ui.label("脚本中 `sustain` 跟随每个频点的速度");
+			});
+			ui.horizontal(|ui| {
+				ui.label("包络释放");
+				ui.add(ParamSlider::for_param(&params.sustain_release, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("谐波数量");
+				ui.add(ParamSlider::for_param(&params.harmonics, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("谐波衰减");
+				ui.add(ParamSlider::for_param(&params.harmonic_rolloff, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("频谱阈值");
+				ui.add(ParamSlider::for_param(&params.spectral_threshold, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("频谱比率");
+				ui.add(ParamSlider::for_param(&params.spectral_ratio, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("频谱动态");
+				ui.add(ParamSlider::for_param(&params.spectral_dynamics, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("频谱补偿增益");
+				ui.add(ParamSlider::for_param(&params.spectral_makeup, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("随机种子");
+				ui.add(ParamSlider::for_param(&params.seed, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("振幅使用分贝");
+				ui.add(ParamSlider::for_param(&params.magnitude_in_db, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("峰值插值");
+				ui.add(ParamSlider::for_param(&params.peak_interpolation, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("相位锁定");
+				ui.add(ParamSlider::for_param(&params.phase_lock, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("能量保持");
+				ui.add(ParamSlider::for_param(&params.preserve_energy, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("自动补偿增益");
+				ui.add(ParamSlider::for_param(&params.auto_makeup, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("越界处理");
+				ui.add(ParamSlider::for_param(&params.range_mode, setter));
+			});
 			ui.horizontal(|ui| {
 				ui.label("FFT 窗长");
 				ui.add(ParamSlider::for_param(&params.window_size, setter));
+				latency_ui(ui, params, spectrum, "延迟和 CPU 占用较高");
+				load_ui(ui, meters);
+			});
+			ui.horizontal(|ui| {
+				ui.label("低延迟模式");
+				ui.add(ParamSlider::for_param(&params.low_latency, setter));
+				ui.label("延迟减半，频谱变化时音质更粗糙");
+			});
+			ui.horizontal(|ui| {
+				ui.label("补零倍数");
+				ui.add(ParamSlider::for_param(&params.zero_pad, setter));
+				ui.label("频率更精细，CPU 占用更高");
 			});
 			ui.horizontal(|ui| {
 				ui.label("窗口参数");
 				ui.add(ParamSlider::for_param(&params.window_factor, setter));
+				overlap_add_ui(ui, setter, params, ["重叠相加不平坦", "自动", "选择最近的能平坦重建的窗口参数"]);
 			});
 			ui.horizontal(|ui| {
 				ui.label("窗口延迟");
 				ui.add(ParamSlider::for_param(&params.window_offset, setter));
 			});
+			ui.horizontal(|ui| {
+				ui.label("脚本控制窗口");
+				ui.add(ParamSlider::for_param(&params.script_window, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("低/中分频点");
+				ui.add(ParamSlider::for_param(&params.crossover_low, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("中/高分频点");
+				ui.add(ParamSlider::for_param(&params.crossover_high, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("处理下限");
+				ui.add(ParamSlider::for_param(&params.min_freq, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("处理上限");
+				ui.add(ParamSlider::for_param(&params.max_freq, setter));
+			});
+			ui.horizontal(|ui| {
+				ui.label("范围外频点");
+				ui.add(ParamSlider::for_param(&params.out_of_band, setter));
+				ui.label("处理范围以外的频点不运行脚本");
+			});
 		})});
 
 		egui::ScrollArea::both().show(ui, |ui| {
 			ui.allocate_space(Vec2::new(ui.available_width(), 3.0));
+			preset_ui(ui, setter, params, state, ["预设", "（未选择）", "保存预设", "加载预设", "刷新"]);
+			ab_ui(ui, setter, params, state, ["对比", "复制 A→B", "随工程保存"]);
+			#[cfg(feature = "osc")]
+			osc_ui(ui, setter, params, state, ["OSC", "监听", "端口"]);
 			ui.label("映射器边栏");
 			ui.separator();
 			ui.label(format!("将会从 `{}` 读取映射脚本", PATH_TO_READ.to_string_lossy()));
 			ui.allocate_space(Vec2::new(0.0, 1.0));
+			script_slot_ui(
+				ui, 
+				&mut state.script_slot, 
+				["全部声道", "右声道", "为空时右声道使用主脚本", "为空时该频段使用声道的脚本"],
+				["低频段", "中频段", "高频段"]
+			);
 			ui.horizontal(|ui| {
 				if ui.button("加载").clicked() {
 					let path = &*PATH_TO_READ;
 					// path.push("mapper.rhai");
 					let code = std::fs::read_to_string(path).map_err(|err| format!("{}", err));
-					*params.map_code.write().unwrap() = code;
-					*params.date.write().unwrap() = OffsetDateTime::now_utc().to_string();
+					state.history.replace(params, state.script_slot, code);
 				}
 				if ui.button("清空 (双击)").double_clicked() {
-					*params.map_code.write().unwrap() = Ok(String::new());
-					*params.date.write().unwrap() = OffsetDateTime::now_utc().to_string();
+					state.history.replace(params, state.script_slot, Ok(String::new()));
 				}
 				if ui.button("展示代码").clicked() {
 					state.show_code = !state.show_code;
 				}
+				history_ui(ui, params, &mut state.history, ["撤销", "重做"]);
+				examples_ui(ui, params, &mut state.history, state.script_slot, "示例", ["升八度", "频移", "音阶吸附", "频谱门", "和弦吸附"]);
 			});
+			#[cfg(feature = "url")]
+			url_ui(ui, params, state, ["https://…/script.rhai", "从网址加载"]);
 			ui.allocate_space(Vec2::new(0.0, 1.0));
-			let code_info = params.map_code.read().unwrap();
+			let code_info = params.script(state.script_slot).read().unwrap();
 			match &*code_info {
 				Ok(inner) => {
 					if inner.is_empty() {
@@ -502,6 +2756,10 @@ fn zh_cn_ui(
 					ui.label(e);
 				}
 			}
+			if let Some(e) = &*params.script_error.read().unwrap() {
+				ui.colored_label(egui::Color32::YELLOW, "运行时出错，出错的频点已原样通过：");
+				ui.label(e);
+			}
 
 			ui.allocate_space(Vec2::new(0.0, 8.0));
 			ui.label("代码展示");
@@ -524,13 +2782,31 @@ fn zh_cn_ui(
 			}else {
 				ui.label("已折叠");
 			}
+
+			ui.allocate_space(Vec2::new(0.0, 8.0));
+			reference_ui(ui, ["脚本参考", "变量", "函数"], |entry| entry.zh_cn);
 		});
 	});
 }
 
+impl ClapPlugin for Interface {
+	const CLAP_ID: &'static str = "com.iamplugins.i-am-freq-remapper";
+	const CLAP_DESCRIPTION: Option<&'static str> = Some("Phase vocoder based frequency remapper driven by Rhai scripts");
+	const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+	const CLAP_SUPPORT_URL: Option<&'static str> = None;
+	const CLAP_FEATURES: &'static [ClapFeature] = &[
+		ClapFeature::AudioEffect,
+		ClapFeature::PitchShifter,
+		ClapFeature::FrequencyShifter,
+		ClapFeature::Stereo,
+		ClapFeature::Mono,
+	];
+}
+
 impl Vst3Plugin for Interface {
 	const VST3_CLASS_ID: [u8; 16] = *b"IAmFreqRemapper_";
 	const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] = &[Vst3SubCategory::Fx, Vst3SubCategory::Tools];
 }
 
+nih_export_clap!(Interface);
 nih_export_vst3!(Interface);
\ No newline at end of file
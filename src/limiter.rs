@@ -0,0 +1,55 @@
+use crate::phase_vocoder::to_db;
+use crate::phase_vocoder::from_db;
+
+// the soft knee spans this many dB, centred on the threshold
+const KNEE_DB: f32 = 6.0;
+
+/// A peak limiter without lookahead for the plugin's output. Gain reduction follows a peak above
+/// the threshold at once and lets go over the release time, so the output never goes past the
+/// threshold; the knee eases in the reduction from `KNEE_DB / 2` below it.
+pub struct Limiter {
+	// in dB, 0 or more
+	reduction: f32,
+}
+
+impl Default for Limiter {
+	fn default() -> Self {
+		Self {
+			reduction: 0.0,
+		}
+	}
+}
+
+impl Limiter {
+	/// The gain for a sample whose peak over all channels is `peak`, with the threshold in dB and
+	/// the release in ms. Called once per sample, linked across channels so the image doesn't move.
+	pub fn next(&mut self, peak: f32, threshold: f32, release: f32, sample_rate: f32) -> f32 {
+		let release_coeff = (-1000.0 / (release * sample_rate).max(1.0)).exp();
+		let target = static_reduction(to_db(peak), threshold);
+		self.reduction = target.max(self.reduction * release_coeff);
+		from_db(-self.reduction)
+	}
+
+	/// The gain reduction of the last sample, in dB.
+	pub fn reduction(&self) -> f32 {
+		self.reduction
+	}
+
+	pub fn reset(&mut self) {
+		*self = Self::default();
+	}
+}
+
+/// How far a peak at `level` dB has to come down, with an infinite ratio above the knee. Through
+/// the knee the output still rises with the input, just ever more slowly, and reaches the threshold
+/// at its top, so nothing comes out above it.
+fn static_reduction(level: f32, threshold: f32) -> f32 {
+	let over = level - threshold;
+	if over <= -KNEE_DB / 2.0 {
+		0.0
+	}else if over < KNEE_DB / 2.0 {
+		(over + KNEE_DB / 2.0).powi(2) / (2.0 * KNEE_DB)
+	}else {
+		over
+	}
+}
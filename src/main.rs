@@ -0,0 +1,6 @@
+use nih_plug::prelude::*;
+use i_am_freq_remapper::Interface;
+
+fn main() {
+	nih_export_standalone::<Interface>();
+}
@@ -0,0 +1,123 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+const METER_CHANNELS: usize = 2;
+const PEAK_DECAY_DB_PER_SECOND: f32 = 12.0;
+const RMS_TIME: f32 = 0.3;
+const LOAD_TIME: f32 = 0.5;
+
+/// Peak-hold and RMS level of one signal. Written by the audio thread once per block,
+/// read by the editor; a single writer means plain loads and stores are enough.
+#[derive(Default)]
+pub struct Level {
+	peak: AtomicU32,
+	mean_square: AtomicU32,
+}
+
+impl Level {
+	/// Folds a block into the level and returns the block's own peak.
+	pub fn update(&self, samples: &[f32], sample_rate: f32) -> f32 {
+		if samples.is_empty() || sample_rate <= 0.0 {
+			return 0.0
+		}
+
+		let (peak, sum) = samples.iter().fold((0.0_f32, 0.0_f32), |(peak, sum), sample| {
+			(peak.max(sample.abs()), sum + sample * sample)
+		});
+		let seconds = samples.len() as f32 / sample_rate;
+
+		let held = self.peak() * 10.0_f32.powf(-PEAK_DECAY_DB_PER_SECOND * seconds / 20.0);
+		self.peak.store(peak.max(held).to_bits(), Ordering::Relaxed);
+
+		let mean_square = self.mean_square();
+		let coeff = 1.0 - (-seconds / RMS_TIME).exp();
+		let mean_square = mean_square + (sum / samples.len() as f32 - mean_square) * coeff;
+		self.mean_square.store(mean_square.to_bits(), Ordering::Relaxed);
+
+		peak
+	}
+
+	pub fn peak(&self) -> f32 {
+		f32::from_bits(self.peak.load(Ordering::Relaxed))
+	}
+
+	pub fn mean_square(&self) -> f32 {
+		f32::from_bits(self.mean_square.load(Ordering::Relaxed))
+	}
+
+	pub fn rms(&self) -> f32 {
+		self.mean_square().sqrt()
+	}
+}
+
+#[derive(Default)]
+pub struct ChannelMeter {
+	pub input: Level,
+	pub output: Level,
+}
+
+/// Input and output levels of every channel, a latching clip indicator, the processing load and
+/// the output limiter's gain reduction.
+#[derive(Default)]
+pub struct Meters {
+	channels: [ChannelMeter; METER_CHANNELS],
+	channel_count: AtomicUsize,
+	clipped: AtomicBool,
+	load: AtomicU32,
+	gain_reduction: AtomicU32,
+}
+
+impl Meters {
+	pub fn channel(&self, idx: usize) -> Option<&ChannelMeter> {
+		self.channels.get(idx)
+	}
+
+	pub fn set_channel_count(&self, channel_count: usize) {
+		self.channel_count.store(channel_count.min(METER_CHANNELS), Ordering::Relaxed);
+	}
+
+	/// Meters of the channels the plugin is currently processing.
+	pub fn active_channels(&self) -> &[ChannelMeter] {
+		&self.channels[..self.channel_count.load(Ordering::Relaxed)]
+	}
+
+	pub fn clip(&self) {
+		self.clipped.store(true, Ordering::Relaxed);
+	}
+
+	pub fn clear_clip(&self) {
+		self.clipped.store(false, Ordering::Relaxed);
+	}
+
+	pub fn clipped(&self) -> bool {
+		self.clipped.load(Ordering::Relaxed)
+	}
+
+	/// Folds in a block that took `elapsed` seconds to process and holds `duration` seconds of audio.
+	pub fn update_load(&self, elapsed: f32, duration: f32) {
+		if duration <= 0.0 {
+			return
+		}
+
+		let load = self.load();
+		let coeff = 1.0 - (-duration / LOAD_TIME).exp();
+		self.load.store((load + (elapsed / duration - load) * coeff).to_bits(), Ordering::Relaxed);
+	}
+
+	/// Time spent processing as a share of the audio it produced, smoothed over `LOAD_TIME` seconds;
+	/// 1.0 means the plugin only just keeps up in real time.
+	pub fn load(&self) -> f32 {
+		f32::from_bits(self.load.load(Ordering::Relaxed))
+	}
+
+	/// The most the limiter took off during the last buffer, in dB.
+	pub fn set_gain_reduction(&self, reduction: f32) {
+		self.gain_reduction.store(reduction.to_bits(), Ordering::Relaxed);
+	}
+
+	pub fn gain_reduction(&self) -> f32 {
+		f32::from_bits(self.gain_reduction.load(Ordering::Relaxed))
+	}
+}
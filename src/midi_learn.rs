@@ -0,0 +1,89 @@
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// How many params a CC can be learned for: the macros `a` to `d`.
+pub const LEARNABLE: usize = 4;
+// stands for no CC, and for nothing being learned; CC numbers only go up to 127
+const NONE: u8 = u8::MAX;
+
+/// The CC bound to each macro, as saved with the project.
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CcBindings(pub [Option<u8>; LEARNABLE]);
+
+/// MIDI learn for the macros. The audio thread hands every CC it gets to [`MidiLearn::handle_cc`],
+/// which only touches atomics and tells it which macro the CC drives; the editor only learns and
+/// forgets bindings.
+pub struct MidiLearn {
+	bindings: [AtomicU8; LEARNABLE],
+	learning: AtomicU8,
+}
+
+impl Default for MidiLearn {
+	fn default() -> Self {
+		Self {
+			bindings: [const { AtomicU8::new(NONE) }; LEARNABLE],
+			learning: AtomicU8::new(NONE),
+		}
+	}
+}
+
+fn index_of(value: u8) -> Option<usize> {
+	(value != NONE).then_some(value as usize)
+}
+
+impl MidiLearn {
+	/// Binds the next CC to come in to macro `index`, or stops waiting if it already was.
+	pub fn learn(&self, index: usize) {
+		let _ = self.learning.fetch_update(Ordering::AcqRel, Ordering::Acquire, |learning| {
+			Some(if learning == index as u8 { NONE } else { index as u8 })
+		});
+	}
+
+	/// The macro waiting for a CC, if any.
+	pub fn learning(&self) -> Option<usize> {
+		index_of(self.learning.load(Ordering::Acquire))
+	}
+
+	/// Unbinds macro `index`, and stops waiting for a CC for it.
+	pub fn forget(&self, index: usize) {
+		let _ = self.learning.compare_exchange(index as u8, NONE, Ordering::AcqRel, Ordering::Acquire);
+		self.bindings[index].store(NONE, Ordering::Release);
+	}
+
+	pub fn binding(&self, index: usize) -> Option<u8> {
+		let cc = self.bindings[index].load(Ordering::Acquire);
+		(cc != NONE).then_some(cc)
+	}
+
+	pub fn bindings(&self) -> CcBindings {
+		CcBindings(std::array::from_fn(|index| self.binding(index)))
+	}
+
+	/// Replaces every binding, as when a project is loaded. Numbers that aren't CCs are dropped.
+	pub fn set_bindings(&self, bindings: CcBindings) {
+		for (binding, cc) in self.bindings.iter().zip(bindings.0) {
+			binding.store(cc.filter(|&cc| cc < 128).unwrap_or(NONE), Ordering::Release);
+		}
+	}
+
+	/// Called on the audio thread for every CC. While a macro is learning, the CC is bound to it and
+	/// taken away from any other macro it drove, so one knob never moves two of them. Returns the
+	/// macro the CC drives, if any.
+	pub fn handle_cc(&self, cc: u8) -> Option<usize> {
+		if cc >= 128 {
+			return None;
+		}
+		if let Some(index) = index_of(self.learning.swap(NONE, Ordering::AcqRel)) {
+			for (other, binding) in self.bindings.iter().enumerate() {
+				if other != index && binding.load(Ordering::Acquire) == cc {
+					binding.store(NONE, Ordering::Release);
+				}
+			}
+			self.bindings[index].store(cc, Ordering::Release);
+		}
+
+		self.bindings.iter().position(|binding| binding.load(Ordering::Acquire) == cc)
+	}
+}
@@ -0,0 +1,67 @@
+/// MIDI notes held down, and which one scripts see as `note_freq` / `note_vel`.
+/// Fixed size, so it can be updated on the audio thread.
+pub struct HeldNotes {
+	velocity: [f32; 128],
+	// the note scripts follow while anything is held
+	current: Option<u8>,
+	// what `current` last was, kept after everything is released so tails stay in tune
+	last: Option<u8>,
+}
+
+impl Default for HeldNotes {
+	fn default() -> Self {
+		Self {
+			velocity: [0.0; 128],
+			current: None,
+			last: None,
+		}
+	}
+}
+
+impl HeldNotes {
+	/// The newest note always takes over. A velocity of 0 is a note-off, as in MIDI.
+	pub fn note_on(&mut self, note: u8, velocity: f32) {
+		if velocity <= 0.0 {
+			return self.note_off(note);
+		}
+		let Some(slot) = self.velocity.get_mut(note as usize) else {
+			return;
+		};
+
+		*slot = velocity;
+		self.current = Some(note);
+		self.last = Some(note);
+	}
+
+	/// Releasing the followed note falls back to the lowest one still held.
+	pub fn note_off(&mut self, note: u8) {
+		let Some(slot) = self.velocity.get_mut(note as usize) else {
+			return;
+		};
+
+		*slot = 0.0;
+		if self.current == Some(note) {
+			self.current = self.velocity.iter().position(|velocity| *velocity > 0.0).map(|note| note as u8);
+			if self.current.is_some() {
+				self.last = self.current;
+			}
+		}
+	}
+
+	pub fn clear(&mut self) {
+		*self = Self::default();
+	}
+
+	/// Frequency (A4 = note 69 = 440 Hz, equal temperament) and velocity of the followed note.
+	/// With nothing held it's the last note played at velocity 0, or both 0 before any note.
+	pub fn current(&self) -> (f32, f32) {
+		match self.last {
+			Some(note) => (note_frequency(note), self.current.map_or(0.0, |note| self.velocity[note as usize])),
+			None => (0.0, 0.0),
+		}
+	}
+}
+
+pub fn note_frequency(note: u8) -> f32 {
+	440.0 * 2.0_f32.powf((note as f32 - 69.0) / 12.0)
+}
@@ -0,0 +1,142 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Messages to `/freqremap/a` .. `/freqremap/d` set the macro params.
+pub const OSC_PREFIX: &str = "/freqremap/";
+pub const OSC_MACROS: [&str; 4] = ["a", "b", "c", "d"];
+const DEFAULT_PORT: u16 = 9000;
+
+/// Saved with the project, so a performance setup comes back listening.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OscSettings {
+	pub enabled: bool,
+	pub port: u16,
+}
+
+impl Default for OscSettings {
+	fn default() -> Self {
+		Self {
+			enabled: false,
+			port: DEFAULT_PORT,
+		}
+	}
+}
+
+#[cfg(feature = "osc")]
+pub use server::OscServer;
+
+#[cfg(feature = "osc")]
+mod server {
+	use std::net::UdpSocket;
+	use std::sync::Arc;
+	use std::sync::atomic::AtomicBool;
+	use std::sync::atomic::AtomicU32;
+	use std::sync::atomic::Ordering;
+	use std::thread::JoinHandle;
+	use std::time::Duration;
+	use rosc::OscPacket;
+	use rosc::OscType;
+	use super::OSC_MACROS;
+	use super::OSC_PREFIX;
+
+	// how often the listener checks whether it should stop, which is also how long closing it may block
+	const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+	/// The latest value received for each macro, until the editor hands it to the host.
+	#[derive(Default)]
+	struct Pending {
+		values: [AtomicU32; 4],
+		fresh: [AtomicBool; 4],
+	}
+
+	/// A UDP listener on its own thread. It only ever writes to [`Pending`]; the params are set
+	/// by the editor through the host, so neither side comes near the audio thread.
+	pub struct OscServer {
+		pending: Arc<Pending>,
+		stop: Arc<AtomicBool>,
+		thread: Option<JoinHandle<()>>,
+	}
+
+	impl OscServer {
+		pub fn start(port: u16) -> Result<Self, String> {
+			let socket = UdpSocket::bind(("0.0.0.0", port)).map_err(|e| format!("{e}"))?;
+			socket.set_read_timeout(Some(POLL_INTERVAL)).map_err(|e| format!("{e}"))?;
+
+			let pending = Arc::new(Pending::default());
+			let stop = Arc::new(AtomicBool::new(false));
+			let thread = std::thread::Builder::new()
+				.name(String::from("freqremap osc"))
+				.spawn({
+					let pending = pending.clone();
+					let stop = stop.clone();
+					move || listen(socket, &pending, &stop)
+				})
+				.map_err(|e| format!("{e}"))?;
+
+			Ok(Self {
+				pending,
+				stop,
+				thread: Some(thread),
+			})
+		}
+
+		/// Takes the macros that changed since the last call, as `(index, value)`.
+		pub fn take(&self) -> impl Iterator<Item = (usize, f32)> + '_ {
+			(0..OSC_MACROS.len())
+				.filter(|&i| self.pending.fresh[i].swap(false, Ordering::Acquire))
+				.map(|i| (i, f32::from_bits(self.pending.values[i].load(Ordering::Relaxed))))
+		}
+	}
+
+	impl Drop for OscServer {
+		fn drop(&mut self) {
+			self.stop.store(true, Ordering::Relaxed);
+			if let Some(thread) = self.thread.take() {
+				let _ = thread.join();
+			}
+		}
+	}
+
+	fn listen(socket: UdpSocket, pending: &Pending, stop: &AtomicBool) {
+		let mut buf = [0_u8; rosc::decoder::MTU];
+		while !stop.load(Ordering::Relaxed) {
+			// timeouts and packets that don't decode alike just go back to waiting
+			let Ok(len) = socket.recv(&mut buf) else {
+				continue;
+			};
+			if let Ok((_, packet)) = rosc::decoder::decode_udp(&buf[..len]) {
+				handle(&packet, pending);
+			}
+		}
+	}
+
+	fn handle(packet: &OscPacket, pending: &Pending) {
+		match packet {
+			OscPacket::Message(message) => {
+				let Some(index) = message.addr.strip_prefix(OSC_PREFIX)
+					.and_then(|name| OSC_MACROS.iter().position(|macro_name| *macro_name == name))
+				else {
+					return;
+				};
+				let value = match message.args.first() {
+					Some(OscType::Float(value)) => *value,
+					Some(OscType::Double(value)) => *value as f32,
+					Some(OscType::Int(value)) => *value as f32,
+					Some(OscType::Long(value)) => *value as f32,
+					_ => return,
+				};
+				if !value.is_finite() {
+					return;
+				}
+
+				pending.values[index].store(value.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+				pending.fresh[index].store(true, Ordering::Release);
+			},
+			OscPacket::Bundle(bundle) => {
+				for packet in &bundle.content {
+					handle(packet, pending);
+				}
+			},
+		}
+	}
+}
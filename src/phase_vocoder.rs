@@ -1,209 +1,1248 @@
 use rhai::packages::Package;
+use std::collections::HashMap;
+use std::cell::Cell;
+use std::cell::RefCell;
 use std::hash::BuildHasher;
 use std::hash::RandomState;
+use std::ops::RangeInclusive;
 use rhai::Scope;
 use rhai::AST;
+use rhai::CallFnOptions;
+use rhai::Dynamic;
 use rhai::Engine;
-use rustfft::FftPlanner;
+use rhai::Map;
+use realfft::RealFftPlanner;
+use realfft::RealToComplex;
+use realfft::ComplexToReal;
 use std::f32::consts::PI;
+use std::f32::consts::SQRT_2;
 use crate::ring_buffer::RingBuffer;
-use rustfft::Fft;
-use rustfft::num_complex::Complex;
+use crate::spectrum::SpectrumData;
+use crate::triple_buffer::Publisher;
+use crate::rng::ScriptRng;
+use crate::freeze::Freeze;
+use crate::freeze::MorphSlot;
+use crate::freeze::Snapshot;
+use crate::denoise::NoiseProfile;
+use realfft::num_complex::Complex;
 use crate::Arc;
 use rhai_rand::RandomPackage;
 
-const OVERLAP_RATIO: usize = 4;
+pub const OVERLAP_RATIO: usize = 4;
+pub const MAX_WINDOW_SIZE: usize = 32768;
+pub const MAX_STRETCH: f32 = 4.0;
+pub const MAX_ZERO_PAD: usize = 8;
+/// How many bands the spectrum is split into, each of which may run its own script.
+pub const BANDS: usize = 3;
+// how far behind the live input a stretched analysis may fall before jumping back, in samples
+const STRETCH_HISTORY: usize = 1 << 17;
+const CEPSTRUM_LIFTER_RATIO: usize = 32;
+const MAGNITUDE_FLOOR: f32 = 1e-6;
+const BYPASS_FADE_MS: f32 = 10.0;
+// how long the output takes to fade out before a new window size or padding, and back in after
+const RESIZE_FADE_MS: f32 = 10.0;
+const FREEZE_FADE_MS: f32 = 50.0;
+// the exciter looks for its fundamental below this, in Hz
+const EXCITER_MAX_FUNDAMENTAL: f32 = 1000.0;
+// bins either side of the fundamental's peak copied into each harmonic, about a Hann main lobe
+const EXCITER_LOBE: usize = 2;
+/// Anything quieter reads as this in [`to_db`], and maps back to silence in [`from_db`].
+pub const DB_FLOOR: f32 = -120.0;
+// the most a script's `gain_db` may boost a bin by
+const MAX_GAIN_DB: f32 = 48.0;
+// how many bins of the unpadded window share a direction in `decorrelate`; flipping it from one
+// bin to the next would spread the added part over the whole frame, where the window takes most
+// of it away again
+const DECORRELATION_GROUP: usize = 8;
+// the least `spread` radius a phase locked partial is moved by a fraction of a bin with; a
+// narrower kernel, or splitting it between two bins, loses level at the ends of the frame, which
+// the flatter windows of a high `window_factor` give more weight to
+const LOCKED_SPREAD_RADIUS: f32 = 8.0;
 
 lazy_static::lazy_static! {
 	static ref RHAI_ENGINE: Engine = {
 		let mut engine = Engine::new();
 		engine.register_global_module(RandomPackage::new().as_shared_module());
+		crate::rng::register(&mut engine);
+		crate::bins::register(&mut engine);
+		crate::inharmonicity::register(&mut engine);
+		engine.register_fn("to_db", to_db);
+		engine.register_fn("from_db", from_db);
 		engine
 	};
 	static ref HASHER: RandomState = RandomState::new();
 	static ref EMPTY_HASH: u64 = HASHER.hash_one("");
 }
 
+/// What happens to a bin mapped below 0 Hz or above Nyquist. Scripts pick one per bin by
+/// writing its number to `range_mode`.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RangeMode {
+	/// Left out, as if its magnitude were 0.
+	#[default]
+	Drop,
+	/// Pinned to the lowest or highest bin that can hold a tone, so the energy piles up at the edge.
+	Clamp,
+	/// Taken modulo Nyquist, so whatever runs off the top comes back in from the bottom.
+	Wrap,
+}
+
+impl RangeMode {
+	pub const ALL: [RangeMode; 3] = [RangeMode::Drop, RangeMode::Clamp, RangeMode::Wrap];
+
+	/// Where a bin mapped to `frequency` ends up, or `None` to leave it out. Frequencies that
+	/// aren't numbers are always left out.
+	fn apply(self, frequency: f32, bin_width: f32, nyquist: f32) -> Option<f32> {
+		if !frequency.is_finite() {
+			return None;
+		}
+		// Nyquist itself is the last bin, which an identity mapping has to keep
+		if (0.0..=nyquist).contains(&frequency) {
+			return Some(frequency);
+		}
+		match self {
+			RangeMode::Drop => None,
+			// DC and Nyquist only hold a real value, so a tone there would come and go with its phase
+			RangeMode::Clamp => Some(frequency.clamp(bin_width, nyquist - bin_width)),
+			RangeMode::Wrap => Some(frequency.rem_euclid(nyquist)),
+		}
+	}
+}
+
+/// Which side of `spectral_threshold` the per-bin dynamics work on. Each bin is treated on its own,
+/// frame by frame, against its level as the spectrum view shows it.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpectralDynamics {
+	/// Bins above the threshold only rise 1 dB for every `ratio` dB, which tames resonances.
+	#[default]
+	Compress,
+	/// Bins below the threshold fall `ratio` dB for every dB, a gate that closes gradually.
+	Expand,
+}
+
+impl SpectralDynamics {
+	pub const ALL: [SpectralDynamics; 2] = [SpectralDynamics::Compress, SpectralDynamics::Expand];
+
+	/// The change in dB for a bin at `level` dB.
+	pub fn gain(self, level: f32, threshold: f32, ratio: f32) -> f32 {
+		match self {
+			SpectralDynamics::Compress => (threshold - level).min(0.0) * (1.0 - 1.0 / ratio),
+			SpectralDynamics::Expand => (level - threshold).min(0.0) * (ratio - 1.0),
+		}
+	}
+}
+
+/// What happens to the bins outside of `min_freq` and `max_freq`, which skip the scripts and
+/// everything else done bin by bin.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutOfBand {
+	/// Passed through as they came in, the way an empty script leaves them.
+	#[default]
+	Pass,
+	/// Left out, which makes the range a crude band pass.
+	Mute,
+}
+
+impl OutOfBand {
+	pub const ALL: [OutOfBand; 2] = [OutOfBand::Pass, OutOfBand::Mute];
+}
+
 #[derive(Default)]
 pub struct InputParams {
 	pub daw_values: [f32; 4],
-	// pub sustain_values: [f32; 4],
+	/// How long, in milliseconds, each bin's `sustain` takes to rise and fall most of the way
+	/// towards its magnitude; 0 follows it at once.
+	pub sustain_attack: f32,
+	pub sustain_release: f32,
 	pub current_track_id: usize,
+	/// How many channels are processed side by side; `pan` only applies to a stereo pair.
+	pub channel_count: usize,
+	/// The pair is mid and side rather than left and right, which `pan` can't place a bin between.
+	pub mid_side: bool,
 	pub bpm: f32,
+	pub playing: bool,
+	pub looping: bool,
+	pub beat: f32,
+	pub bar_phase: f32,
 	pub daw_time: f32,
 	pub sys_time: f32, 
 	pub window_size: usize,
+	/// How many times the window's length each frame is padded to with zeros before the FFT,
+	/// a power of two; 0 and 1 both mean no padding.
+	pub zero_pad_factor: usize,
+	/// Reads the output half a window early, see [`latency_for`].
+	pub low_latency: bool,
+	/// Where each band ends and the next starts, in Hz, see [`band_of`].
+	pub crossovers: [f32; BANDS - 1],
+	/// The bins processed, by center frequency in Hz; 0 for `max_freq` has no upper limit.
+	pub min_freq: f32,
+	pub max_freq: f32,
+	pub out_of_band: OutOfBand,
 	pub window_factor: f32,
-	pub window_offset: usize,
+	// how far the window is turned, as a fraction of its length
+	pub window_offset: f32,
 	pub gain: f32,
 	pub sample_rate: f32,
+	pub pitch_shift: f32,
+	pub freq_shift: f32,
+	pub preserve_formants: bool,
+	pub seed: u64,
+	pub whisper: f32,
+	/// How much of each bin the two channels of a pair add and take away in quadrature, 0 to 1.
+	pub decorrelation: f32,
+	pub robotize: bool,
+	/// How many bins past the nearest two a mapped bin is spread over, 0 splitting it between them.
+	pub spread: f32,
+	pub magnitude_in_db: bool,
+	pub peak_interpolation: bool,
+	pub phase_lock: bool,
+	pub smoothing: f32,
+	pub tilt: f32,
+	pub tilt_pivot: f32,
+	pub script_window: bool,
+	pub slew: f32,
+	pub stretch: f32,
+	/// RMS of the sidechain input over the current hop, 0 when it isn't connected.
+	pub sidechain: f32,
+	pub bypass: bool,
+	pub transient_sensitivity: f32,
+	pub note_freq: f32,
+	pub note_vel: f32,
+	/// The root as a pitch class, 0 being C, and a bit for each semitone above it that's in the scale.
+	pub scale_root: usize,
+	pub scale: u16,
+	pub harmonics: usize,
+	pub harmonic_rolloff: f32,
+	pub freeze: bool,
+	/// Where a freeze sits between snapshot A, at 0, and B, at 1, once both are taken.
+	pub morph: f32,
+	/// How long, in seconds, a bin held at its loudest takes to fall by 60 dB; 0 turns the hold off.
+	pub hold_time: f32,
+	/// Adds every frame to the noise profile while held, and throws the last one away when it starts.
+	pub learn_noise: bool,
+	/// How many times the learned noise profile is taken off each bin, 0 leaving them alone.
+	pub denoise: f32,
+	pub blur: f32,
+	pub preserve_energy: bool,
+	/// Undoes what the scripts' `gain_db` does to the frame's energy, so it shapes without getting louder or quieter.
+	pub auto_makeup: bool,
+	pub range_mode: RangeMode,
+	pub spectral_threshold: f32,
+	/// 1 or less leaves the bins alone, make-up gain included.
+	pub spectral_ratio: f32,
+	pub spectral_dynamics: SpectralDynamics,
+	pub spectral_makeup: f32,
+}
+
+/// The variables a script may write back for one bin. Any it leaves alone keep the value they
+/// were pushed with: the bin's own frequency and magnitude, a `bin_gain` of 1 and a `gain_db` of 0.
+struct ScriptOutput {
+	frequency: f32,
+	magnitude: f32,
+	// multiplies the magnitude after the mapping, so it's linear even with `magnitude_in_db`
+	bin_gain: f32,
+	// multiplies it as well, given in dB and kept between silence and `MAX_GAIN_DB`
+	gain_db: f32,
+	// -1 is hard left, 1 hard right
+	pan: f32,
+	range_mode: RangeMode,
+}
+
+impl ScriptOutput {
+	/// A bin as it went in, for when there is no script or it failed.
+	fn unchanged(params: &InputParams, frequency: f32, magnitude: f32) -> Self {
+		Self { frequency, magnitude, bin_gain: 1.0, gain_db: 0.0, pan: 0.0, range_mode: params.range_mode }
+	}
 }
 
+/// Where a bin goes and how loud it is this frame, kept until the phases are locked and the bins
+/// can be put into the output.
+#[derive(Clone, Copy, Default)]
+struct SynthesisBin {
+	magnitude: f32,
+	// the output bin, fractional between the two it's split across
+	index: f32,
+	// gone through the mapping, rather than passed on from outside of the processed range
+	mapped: bool,
+}
+
+/// Where the scripts send one bin, as [`PhaseVocoder::map_bins`] reports it.
+pub struct BinMapping {
+	pub bin: usize,
+	pub input_frequency: f32,
+	pub output_frequency: f32,
+	// with `bin_gain` and `gain_db` applied
+	pub output_magnitude: f32,
+}
+
+/// Which of the optional entry functions a script defines. With `map()` the top level isn't run
+/// at all: `setup()`, if there is one, runs once a frame and `map()` once per bin, both with
+/// `this` holding the variables a bare script would see.
+#[derive(Default, Clone, Copy)]
+struct EntryPoints {
+	setup: bool,
+	map: bool,
+}
+
+impl EntryPoints {
+	fn of(ast: &AST) -> Self {
+		let defines = |name: &str| ast.iter_functions().any(|f| f.name == name && f.params.is_empty());
+		Self {
+			setup: defines("setup"),
+			map: defines("map"),
+		}
+	}
+}
+
+/// A compiled mapping script and the state it keeps between calls, empty until given code.
+struct Script {
+	ast: Option<AST>,
+	entry_points: EntryPoints,
+	// `this` for `setup()` and `map()`; what `setup()` writes to it stays until overwritten
+	state: RefCell<Dynamic>,
+	hash: u64,
+}
+
+impl Default for Script {
+	fn default() -> Self {
+		Self {
+			ast: None,
+			entry_points: EntryPoints::default(),
+			state: RefCell::new(Dynamic::from_map(Map::new())),
+			hash: *EMPTY_HASH,
+		}
+	}
+}
+
+impl Script {
+	// emptied in place, since `reset` mustn't allocate
+	fn clear_state(&self) {
+		let mut state = self.state.borrow_mut();
+		if let Some(mut map) = state.write_lock::<Map>() {
+			map.clear();
+			return;
+		}
+		*state = Dynamic::from_map(Map::new());
+	}
+}
+
+/// The band a bin at `frequency` belongs to: how many crossovers are at or below it. A bin
+/// sitting on a crossover goes to the band above, so every bin is in exactly one band, and
+/// crossovers out of order split the spectrum the same as when sorted.
+pub fn band_of(frequency: f32, crossovers: &[f32; BANDS - 1]) -> usize {
+	crossovers.iter().filter(|&&crossover| frequency >= crossover).count()
+}
+
+/// The center frequencies of the bins that get processed: `min_freq` to `max_freq` either way
+/// round, or everything from `min_freq` up when `max_freq` is 0.
+pub fn processed_range(min_freq: f32, max_freq: f32) -> RangeInclusive<f32> {
+	if max_freq <= 0.0 {
+		min_freq..=f32::INFINITY
+	}else {
+		min_freq.min(max_freq)..=min_freq.max(max_freq)
+	}
+}
+
+type FftPlan = (Arc<dyn RealToComplex<f32>>, Arc<dyn ComplexToReal<f32>>);
+
 pub struct PhaseVocoder {
 	sample_rate: f32,
 	window_size: usize,
+	// the window zero-padded, capped at `MAX_WINDOW_SIZE`; bins are spaced by this, frames by the window
+	fft_size: usize,
+	zero_pad: usize,
+	overlap: usize,
 	frame_hop: usize,
+	low_latency: bool,
 
-	fft: Arc<dyn Fft<f32>>,
-	ifft: Arc<dyn Fft<f32>>,
+	fft_plans: HashMap<usize, FftPlan>,
+	fft: Arc<dyn RealToComplex<f32>>,
+	ifft: Arc<dyn ComplexToReal<f32>>,
 
 	input_buffer: RingBuffer<f32>,
+	// the input again, but long enough for a stretched analysis to lag behind; also where the
+	// latency-matched dry signal comes from while bypassed
+	history: RingBuffer<f32>,
+	stretch_lag: f32,
+	// set by `reset`, so the first stretched frame starts from the analysis phases
+	stretch_restart: bool,
+	// one window, oldest first: every frame is added over all of it, and shifted on by a hop before
+	// the next one; the hop being read is always the one at `output_start`
 	output_buffer: RingBuffer<f32>,
+	// in low latency mode, how much each sample of the hop being read still has to be made up by,
+	// for the frames that haven't reached it yet
+	early_gain: Vec<f32>,
 	prev_analysis_phase: Vec<f32>,
-	// prev_synthesis_phase: Vec<f32>,
+	prev_magnitude: Vec<f32>,
+	// NaN until a bin has had a target since the last reset
+	prev_target_freq: Vec<f32>,
+	// f64 because a moved or stretched bin accumulates for as long as it stays moved; NaN until a
+	// bin has come out since the last reset or since it went silent
+	prev_synthesis_phase: Vec<f64>,
+	// frames in a row each bin has been down at `DB_FLOOR`, up to the overlap
+	silent_frames: Vec<usize>,
+	// each bin's magnitude through an envelope follower, the script's `sustain`
+	sustain: Vec<f32>,
+	// how far `freq_shift` has turned the output since the last reset
+	shift_phase: f32,
 
 	// window: Vec<f32>,
 	bin_frequencies: Vec<f32>,
 
+	frame_buffer: Vec<f32>,
 	temp_buffer: Vec<Complex<f32>>,
 	output_temp_buffer: Vec<Complex<f32>>,
+	fft_scratch: Vec<Complex<f32>>,
+	cepstrum_buffer: Vec<f32>,
+	cepstrum_spectrum: Vec<Complex<f32>>,
+	envelope: Vec<f32>,
+	peaks: Vec<usize>,
+	locked_phase: Vec<f32>,
+	synthesis: Vec<SynthesisBin>,
+	// magnitudes being blurred, and the other half of each pass
+	blur_magnitude: Vec<f32>,
+	blur_scratch: Vec<f32>,
 
+	// how far into the current hop the input is, and so the output as well
 	input_count: usize,
-	output_count: usize,
 
-	map_ast: Option<AST>,
-	hash: u64
+	script: Script,
+	// run by the bins of their band instead of `script`, unless they're empty
+	band_scripts: [Script; BANDS],
+	// the first error a script ran into while processing, kept until a script is loaded
+	script_error: Option<String>,
+	seed: u64,
+	rng: Cell<ScriptRng>,
+	whisper_rng: ScriptRng,
+	// the window factor used by the current frame, and the one a script asked for, applied next frame
+	window_factor: f32,
+	requested_window_factor: Cell<Option<f32>>,
+	// 0 is fully processed, 1 fully bypassed; moves towards the bypass param over `BYPASS_FADE_MS`
+	bypass_mix: f32,
+	// a new frame layout empties the buffers, so the output fades to silence over `RESIZE_FADE_MS`
+	// before it's switched to, and the input fades back in after, which comes out a window later
+	resize_fade: f32,
+	input_fade: f32,
+	freeze: Freeze,
+	noise_profile: NoiseProfile,
+	// the captured spectrum, and how fast each bin turns, in radians per input sample
+	frozen_magnitude: Vec<f32>,
+	frozen_phase: Vec<f32>,
+	frozen_advance: Vec<f32>,
+	// the spectra a freeze morphs between once both are taken, and which to take from the next frame
+	snapshots: [Snapshot; 2],
+	snapshot_requests: [bool; 2],
+	// each bin at the loudest it's been, falling over `hold_time`, and like a frozen bin, its phase
+	// and how fast it turns, taken when it was last at its loudest
+	peak_hold: Vec<f32>,
+	hold_phase: Vec<f32>,
+	hold_advance: Vec<f32>,
+
+	spectrum: Option<Arc<SpectrumData>>,
+	// where a frame `spectrum` is asked to capture goes, and the room it's put together in
+	capture: Option<Publisher>,
+	capture_frame: Vec<f32>,
+
+	// the window size, offset and factor `ola` was last worked out for, and whether `early_gain` was
+	ola_window: (usize, f32, f32, bool),
+	ola: OverlapAdd,
 }
 
-fn window(window_size: usize, index: usize, offset: usize, window_factor: f32) -> f32 {
-	let index = (index + offset) % window_size;
-	0.5 * (window_factor - (1.0 - window_factor) * (2.0 * PI * index as f32 / window_size as f32).cos())
+// the plugin builds its processors on the main thread and hands them to the audio thread, so a
+// field that can't be sent along has to fail to build here rather than deep inside nih_plug
+const _: () = {
+	fn send<T: Send>() {}
+	let _ = send::<PhaseVocoder>;
+};
+
+/// The latency of a processor: `window_size` samples, or with `low_latency` half that (but at least
+/// a hop). Low latency reads each sample before the later half of the frames overlapping it have
+/// been added, and makes up their level, so an unchanged spectrum is still rebuilt exactly; a changed
+/// one is averaged over half as many frames, which makes the changes rougher and lets more of each
+/// frame's time aliasing through.
+pub fn latency_for(window_size: usize, overlap: usize, low_latency: bool) -> usize {
+	if low_latency {
+		(window_size / 2).max(window_size / overlap.max(1))
+	}else {
+		window_size
+	}
+}
+
+fn round_zero_pad(factor: usize) -> usize {
+	factor.next_power_of_two().min(MAX_ZERO_PAD)
+}
+
+/// The window turned by `offset` of its length. It's periodic, so the offset needs no wrapping and
+/// can be fractional: moving it moves the window smoothly, and it means the same at every window size.
+fn window(window_size: usize, index: usize, offset: f32, window_factor: f32) -> f32 {
+	let phase = index as f32 / window_size as f32 + offset;
+	0.5 * (window_factor - (1.0 - window_factor) * (2.0 * PI * phase).cos())
+}
+
+/// How the squared window adds up over the frames that overlap at any one sample, which is the
+/// gain a frame resynthesised with its analysis phases comes out at. `ripple` is how far that sum
+/// swings across a hop, relative to `gain`: near 0 the window reconstructs perfectly at this overlap
+/// (the COLA condition for analysis times synthesis window), and dividing by `gain` makes it unity.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct OverlapAdd {
+	pub gain: f32,
+	pub ripple: f32,
+}
+
+/// Works out the [`OverlapAdd`] of [`window`] by summing it at every offset into a hop.
+pub fn overlap_add(window_size: usize, overlap: usize, offset: f32, window_factor: f32) -> OverlapAdd {
+	let hop = (window_size / overlap.max(1)).max(1);
+	let (mut min, mut max, mut sum) = (f32::MAX, 0.0_f32, 0.0);
+	for start in 0..hop {
+		let total: f32 = (start..window_size).step_by(hop)
+			.map(|i| window(window_size, i, offset, window_factor).powi(2))
+			.sum();
+		min = min.min(total);
+		max = max.max(total);
+		sum += total;
+	}
+	let gain = sum / hop as f32;
+	OverlapAdd {
+		gain,
+		ripple: if gain > 0.0 { (max - min) / gain } else { 0.0 },
+	}
+}
+
+/// How unevenly the squared window adds up across the offsets into a hop: the variance of the
+/// sums [`overlap_add`] takes, relative to their mean squared, so 0 is a flat reconstruction and
+/// the window's overall level doesn't count.
+pub fn overlap_variance(window_size: usize, overlap: usize, offset: f32, window_factor: f32) -> f32 {
+	let hop = (window_size / overlap.max(1)).max(1);
+	let totals = (0..hop).map(|start| {
+		(start..window_size).step_by(hop)
+			.map(|i| window(window_size, i, offset, window_factor).powi(2) as f64)
+			.sum::<f64>()
+	});
+	relative_variance(totals, hop) as f32
+}
+
+/// The `window_factor` from 0 to 1 whose squared window adds up flattest at this overlap, by
+/// [`overlap_variance`]. Where several are as flat, as every factor is from an overlap of 3 on, it
+/// keeps the one closest to `near`. Allocates, so keep it off the audio thread.
+pub fn flattest_window_factor(window_size: usize, overlap: usize, offset: f32, near: f32) -> f32 {
+	const STEPS: usize = 1000;
+	// the squared window is a quadratic in the factor, so the sums at every factor come from two
+	// sums of the cosine at each offset into the hop, worked out once. In f64, where the factors
+	// that are exactly flat come out well clear of the ones that are only nearly flat
+	let hop = (window_size / overlap.max(1)).max(1);
+	let mut cosines = vec![(0.0_f64, 0.0_f64, 0.0_f64); hop];
+	for i in 0..window_size {
+		let cosine = (std::f64::consts::TAU * (i as f64 / window_size as f64 + offset as f64)).cos();
+		let (sum, squares, count) = &mut cosines[i % hop];
+		*sum += cosine;
+		*squares += cosine * cosine;
+		*count += 1.0;
+	}
+	let variance = |factor: f64| relative_variance(cosines.iter().map(|(sum, squares, count)| {
+		0.25 * (factor * factor * count - 2.0 * factor * (1.0 - factor) * sum + (1.0 - factor).powi(2) * squares)
+	}), hop);
+
+	let factors = (0..=STEPS).map(|step| step as f64 / STEPS as f64);
+	let least = factors.clone().map(variance).fold(f64::MAX, f64::min);
+	// rounding still leaves the flat ones a hair apart
+	factors
+		.filter(|&factor| variance(factor) <= least + 1e-15)
+		.map(|factor| factor as f32)
+		.min_by(|a, b| (a - near).abs().total_cmp(&(b - near).abs()))
+		.unwrap_or(near)
+}
+
+fn relative_variance(values: impl Iterator<Item = f64> + Clone, count: usize) -> f64 {
+	let mean = values.clone().sum::<f64>() / count as f64;
+	if mean <= 0.0 {
+		return 0.0;
+	}
+	values.map(|value| (value / mean - 1.0).powi(2)).sum::<f64>() / count as f64
+}
+
+/// Linear amplitude to decibels, clamped to [`DB_FLOOR`] so silence and negative input stay finite.
+pub fn to_db(value: f32) -> f32 {
+	(20.0 * value.log10()).max(DB_FLOOR)
+}
+
+/// Inverse of [`to_db`]; [`DB_FLOOR`] and below give exactly zero.
+pub fn from_db(db: f32) -> f32 {
+	if db <= DB_FLOOR {
+		0.0
+	}else {
+		10.0_f32.powf(db / 20.0)
+	}
+}
+
+/// This channel's share of a bin panned to `pan`. Equal power, scaled so that the center is unity
+/// on both sides. Every channel runs the script on its own, so the two sides agree on a bin's pan
+/// as long as the script doesn't depend on `sound_channel_id` or the channel's own signal.
+fn pan_gain(params: &InputParams, pan: f32) -> f32 {
+	if params.channel_count != 2 || params.mid_side || pan == 0.0 {
+		return 1.0;
+	}
+
+	let angle = (pan.clamp(-1.0, 1.0) + 1.0) * PI / 4.0;
+	let gain = if params.current_track_id == 0 { angle.cos() } else { angle.sin() };
+	gain * SQRT_2
+}
+
+/// Adds `value`, moved from bin `from` to the fractional bin `index`, to `spectrum` through a
+/// Hann-windowed sinc reaching `radius` bins either side. That's what moving a frame's spectrum by
+/// a fraction of a bin does, so unlike splitting it between the nearest two bins the frame keeps
+/// its level between bins. Each bin a tap is away from `from` turns it by where the window peaks,
+/// `center` samples into a frame of `fft_size`. Taps past the ends are dropped.
+fn spread_bin(spectrum: &mut [Complex<f32>], value: Complex<f32>, from: usize, index: f32, radius: f32, center: usize, fft_size: usize) {
+	let first = (index - radius).floor() as isize + 1;
+	let last = (index + radius).ceil() as isize - 1;
+	let tap = |j: isize| {
+		let distance = j as f32 - index;
+		let sinc = if distance == 0.0 { 1.0 } else { (PI * distance).sin() / (PI * distance) };
+		sinc * (0.5 + 0.5 * (PI * distance / radius).cos())
+	};
+	// scaled to add up to one, so a bin landing on another still comes out as it went in
+	let sum: f32 = (first..=last).map(tap).sum();
+	for j in first.max(0)..=last.min(spectrum.len() as isize - 1) {
+		spectrum[j as usize] += value * center_turn(from, j as usize, center, fft_size) * (tap(j) / sum);
+	}
+}
+
+/// What a bin's phase is turned by when it's moved from bin `from` to bin `to`. A bin's phase is
+/// taken where the frame starts, but a partial's phase is only shared by its bins where the window
+/// peaks, `center` samples into a frame of `fft_size`, so a moved bin has to turn by the difference.
+fn center_turn(from: usize, to: usize, center: usize, fft_size: usize) -> Complex<f32> {
+	// whole turns taken out in integers, as in `robotize`
+	let turn = ((to as isize - from as isize) * center as isize).rem_euclid(fft_size as isize) as f32 / fft_size as f32;
+	Complex::from_polar(1.0, -2.0 * PI * turn)
+}
+
+/// The mean of `input` over `radius` values either side, fewer near the ends, kept as a running sum.
+fn moving_average(input: &[f32], output: &mut [f32], radius: usize) {
+	let len = input.len();
+	let mut sum: f32 = input[..radius.min(len)].iter().sum();
+	for i in 0..len {
+		if i + radius < len {
+			sum += input[i + radius];
+		}
+		if i > radius {
+			sum -= input[i - radius - 1];
+		}
+		let count = (i + radius).min(len - 1) + 1 - i.saturating_sub(radius);
+		output[i] = (sum / count as f32).max(0.0);
+	}
+}
+
+/// Every bin louder than the one below it and at least as loud as the one above, by `magnitude`,
+/// into `peaks`. Neither end counts.
+fn find_peaks(peaks: &mut Vec<usize>, spectrum_size: usize, magnitude: impl Fn(usize) -> f32) {
+	peaks.clear();
+	for k in 1..spectrum_size.saturating_sub(1) {
+		let peak = magnitude(k);
+		if peak > magnitude(k - 1) && peak >= magnitude(k + 1) {
+			peaks.push(k);
+		}
+	}
+}
+
+/// The bins the `i`th of `peaks` stands for, split halfway between it and its neighbours. DC
+/// belongs to none of them.
+fn peak_region(peaks: &[usize], i: usize, spectrum_size: usize) -> RangeInclusive<usize> {
+	let peak = peaks[i];
+	let start = if i == 0 { 1 } else { (peaks[i - 1] + peak) / 2 + 1 };
+	let end = peaks.get(i + 1).map_or(spectrum_size - 1, |next| (peak + next) / 2);
+	start..=end
+}
+
+/// Wraps a phase into `[-PI, PI)`.
+fn principal_argument(phase: f32) -> f32 {
+	(phase + PI).rem_euclid(2.0 * PI) - PI
+}
+
+/// [`principal_argument`] for the phases worked out in `f64`.
+fn principal_argument_f64(phase: f64) -> f64 {
+	use std::f64::consts::PI;
+	(phase + PI).rem_euclid(2.0 * PI) - PI
 }
 
 impl PhaseVocoder {
 	pub fn new(window_size: usize, sample_rate: f32) -> Self {
-		let window_size = window_size.next_power_of_two();
-		let window_size = window_size.max(OVERLAP_RATIO);
+		Self::with_overlap(window_size, OVERLAP_RATIO, sample_rate)
+	}
 
-		let frame_hop = window_size / OVERLAP_RATIO;
+	/// Same as [`PhaseVocoder::new`], but with `overlap` frames per window instead of the default 4.
+	/// Both `window_size` and `overlap` are rounded up to powers of two, and `window_size` is capped
+	/// at [`MAX_WINDOW_SIZE`].
+	///
+	/// Every buffer and FFT plan for every supported window size is allocated here, so that
+	/// [`PhaseVocoder::renew_window_size`] can run on the audio thread without allocating.
+	pub fn with_overlap(window_size: usize, overlap: usize, sample_rate: f32) -> Self {
+		let overlap = overlap.next_power_of_two().min(MAX_WINDOW_SIZE);
+		let window_size = window_size.next_power_of_two().clamp(overlap, MAX_WINDOW_SIZE);
 
-		let input_buffer = RingBuffer::new(window_size);
-		let output_buffer = RingBuffer::new(window_size);
+		let frame_hop = window_size / overlap;
 
-		let bin_frequencies = (0..window_size).map(|k| k as f32 * sample_rate / window_size as f32).collect();
+		let mut input_buffer = RingBuffer::with_pow2_capacity(MAX_WINDOW_SIZE);
+		let history = RingBuffer::with_pow2_capacity(STRETCH_HISTORY);
+		let mut output_buffer = RingBuffer::with_pow2_capacity(MAX_WINDOW_SIZE);
+		input_buffer.set_capacity(window_size);
+		output_buffer.set_capacity(window_size);
 
-		let mut planner = FftPlanner::new();
-		let fft = planner.plan_fft_forward(window_size);
-		let ifft = planner.plan_fft_inverse(window_size);
+		let mut planner = RealFftPlanner::new();
+		let mut fft_plans = HashMap::new();
+		let mut size = overlap;
+		while size <= MAX_WINDOW_SIZE {
+			fft_plans.insert(size, (planner.plan_fft_forward(size), planner.plan_fft_inverse(size)));
+			size *= 2;
+		}
+		let scratch_len = fft_plans.values()
+			.map(|(fft, ifft): &FftPlan| fft.get_scratch_len().max(ifft.get_scratch_len()))
+			.max()
+			.unwrap_or(0);
+		let (fft, ifft) = fft_plans[&window_size].clone();
 
-		let prev_analysis_phase = vec![0.0; window_size];
+		let prev_analysis_phase = vec![0.0; MAX_WINDOW_SIZE];
 
-		let temp_buffer = vec![Complex::ZERO; window_size];
-		let output_temp_buffer = vec![Complex::ZERO; window_size];
+		let spectrum_size = MAX_WINDOW_SIZE / 2 + 1;
+		let frame_buffer = vec![0.0; MAX_WINDOW_SIZE];
+		let temp_buffer = vec![Complex::ZERO; spectrum_size];
+		let output_temp_buffer = vec![Complex::ZERO; spectrum_size];
+		let fft_scratch = vec![Complex::ZERO; scratch_len];
+		let cepstrum_buffer = vec![0.0; MAX_WINDOW_SIZE];
+		let cepstrum_spectrum = vec![Complex::ZERO; spectrum_size];
+		let envelope = vec![1.0; spectrum_size];
+		let peaks = Vec::with_capacity(spectrum_size);
+		let locked_phase = vec![0.0; spectrum_size];
+		let prev_magnitude = vec![0.0; spectrum_size];
+		let prev_target_freq = vec![f32::NAN; spectrum_size];
 
-		Self {
+		let mut vocoder = Self {
 			window_size,
+			fft_size: window_size,
+			zero_pad: 1,
+			overlap,
 			frame_hop,
+			low_latency: false,
 			input_buffer,
+			history,
+			stretch_lag: 0.0,
+			stretch_restart: true,
 			output_buffer,
-			bin_frequencies,
+			early_gain: vec![1.0; MAX_WINDOW_SIZE],
+			bin_frequencies: vec![0.0; MAX_WINDOW_SIZE],
+			fft_plans,
 			fft,
 			ifft,
 			prev_analysis_phase,
+			prev_magnitude,
+			prev_synthesis_phase: vec![f64::NAN; spectrum_size],
+			silent_frames: vec![0; spectrum_size],
+			sustain: vec![0.0; spectrum_size],
+			shift_phase: 0.0,
+			prev_target_freq,
 			sample_rate,
+			frame_buffer,
 			temp_buffer,
 			output_temp_buffer,
+			fft_scratch,
+			cepstrum_buffer,
+			cepstrum_spectrum,
+			envelope,
+			peaks,
+			locked_phase,
+			synthesis: vec![SynthesisBin::default(); spectrum_size],
+			blur_magnitude: vec![0.0; spectrum_size],
+			blur_scratch: vec![0.0; spectrum_size],
 			input_count: 0,
-			output_count: 0,
-			map_ast: None,
-			hash: *EMPTY_HASH
+			script: Script::default(),
+			band_scripts: Default::default(),
+			script_error: None,
+			seed: 0,
+			rng: Cell::new(ScriptRng::new(0)),
+			whisper_rng: ScriptRng::new(!0),
+			window_factor: 0.5,
+			requested_window_factor: Cell::new(None),
+			bypass_mix: 0.0,
+			resize_fade: 1.0,
+			input_fade: 1.0,
+			freeze: Freeze::default(),
+			noise_profile: NoiseProfile::new(spectrum_size),
+			frozen_magnitude: vec![0.0; spectrum_size],
+			frozen_phase: vec![0.0; spectrum_size],
+			frozen_advance: vec![0.0; spectrum_size],
+			snapshots: [Snapshot::new(spectrum_size), Snapshot::new(spectrum_size)],
+			snapshot_requests: [false; 2],
+			peak_hold: vec![0.0; spectrum_size],
+			hold_phase: vec![0.0; spectrum_size],
+			hold_advance: vec![0.0; spectrum_size],
+			spectrum: None,
+			capture: None,
+			capture_frame: Vec::new(),
+			// NaN never compares equal, so the first frame works it out
+			ola_window: (0, f32::NAN, f32::NAN, false),
+			ola: OverlapAdd { gain: 1.0, ripple: 0.0 },
+		};
+		vocoder.update_bin_frequencies();
+		vocoder
+	}
+
+	fn update_bin_frequencies(&mut self) {
+		for (k, frequency) in self.bin_frequencies[..self.fft_size].iter_mut().enumerate() {
+			*frequency = k as f32 * self.sample_rate / self.fft_size as f32;
 		}
 	}
 
+	/// Publishes the input and output magnitude spectrum of every processed frame to `spectrum`.
+	pub fn set_spectrum(&mut self, spectrum: Arc<SpectrumData>) {
+		self.spectrum = Some(spectrum);
+	}
+
+	/// Sends the whole of the next frame after [`SpectrumData::request_capture`] through
+	/// `publisher`, in the layout [`SpectrumData::capture_publisher`] describes.
+	pub fn set_capture(&mut self, publisher: Publisher) {
+		self.capture_frame = vec![0.0; publisher.capacity()];
+		self.capture = Some(publisher);
+	}
+
+	/// Takes the next frame as snapshot `slot`, the way it comes in before any freeze. Once both
+	/// are taken a freeze holds a morph between them, set by `morph`, instead of what it caught.
+	/// Starting over, as for a new window size, forgets them.
+	pub fn capture_snapshot(&mut self, slot: MorphSlot) {
+		self.snapshot_requests[slot as usize] = true;
+	}
+
+	pub fn has_snapshot(&self, slot: MorphSlot) -> bool {
+		self.snapshots[slot as usize].captured()
+	}
+
 	pub fn clear_mapper(&mut self) {
-		self.map_ast = None;
+		self.script.ast = None;
+		self.script_error = None;
+	}
+
+	/// The first error a script ran into while processing since the last one was loaded. The bins
+	/// it failed on were passed on as if there were no script, so a script that only fails now and
+	/// then, say for loud bins, doesn't take the host down with it.
+	pub fn script_error(&self) -> Option<&str> {
+		self.script_error.as_deref()
 	}
 
 	pub fn update_mapping(&mut self, code: impl AsRef<str>) -> Result<(), String> {
-		let code = code.as_ref();
+		self.compile_script(None, code.as_ref())
+	}
+
+	/// Gives the bins of `band` a script of their own. Empty hands them back to the main one.
+	pub fn update_band_mapping(&mut self, band: usize, code: impl AsRef<str>) -> Result<(), String> {
+		self.compile_script(Some(band), code.as_ref())
+	}
+
+	fn script_slot(&mut self, band: Option<usize>) -> &mut Script {
+		match band {
+			Some(band) => &mut self.band_scripts[band],
+			None => &mut self.script,
+		}
+	}
+
+	fn compile_script(&mut self, band: Option<usize>, code: &str) -> Result<(), String> {
 		if code.is_empty() {
-			self.clear_mapper();
-			self.hash = *EMPTY_HASH;
+			let script = self.script_slot(band);
+			let changed = script.hash != *EMPTY_HASH;
+			script.ast = None;
+			script.hash = *EMPTY_HASH;
+			if changed {
+				self.script_error = None;
+			}
 			return Ok(())
 		}
 		let hash = HASHER.hash_one(code);
-		if hash == self.hash {
+		if hash == self.script_slot(band).hash {
 			return Ok(())
 		}
 		let ast = RHAI_ENGINE.compile(code).map_err(|e| format!("{e}"))?;
-		let ori = self.map_ast.replace(ast);
-		if let Err(e) = self.frequency_mapper(&Default::default(), 0.0, 0.0) {
-			self.map_ast = ori;
+		let script = self.script_slot(band);
+		let ori_entry_points = std::mem::replace(&mut script.entry_points, EntryPoints::of(&ast));
+		let ori = script.ast.replace(ast);
+		// the trial run mustn't advance the generator, or the output would depend on when the script was loaded
+		let rng = self.rng.get();
+		let params = InputParams::default();
+		let script = match band {
+			Some(band) => &self.band_scripts[band],
+			None => &self.script,
+		};
+		let result = self.setup_script(script, &params).and_then(|()| self.frequency_mapper(script, &params, 0, 0.0, 0.0));
+		self.rng.set(rng);
+		script.clear_state();
+		let script = self.script_slot(band);
+		if let Err(e) = result {
+			script.ast = ori;
+			script.entry_points = ori_entry_points;
 			return Err(e)
 		}
-		self.hash = hash;
+		script.hash = hash;
+		self.script_error = None;
 
 		Ok(())
 	}
 
+	/// The script bin `k` runs: its band's, or the main one when the band has none.
+	fn script_for(&self, params: &InputParams, k: usize) -> &Script {
+		let band = &self.band_scripts[band_of(self.bin_frequencies[k], &params.crossovers)];
+		if band.ast.is_some() { band } else { &self.script }
+	}
+
+	/// Refreshes the frame's variables in the state `setup()` and `map()` see, and runs `setup()`.
+	/// Does nothing for a bare script, which gets all of its variables afresh for every bin.
+	fn setup_script(&self, script: &Script, params: &InputParams) -> Result<(), String> {
+		let ast = match &script.ast {
+			Some(ast) if script.entry_points.map => ast,
+			_ => return Ok(()),
+		};
+
+		let mut state = script.state.borrow_mut();
+		// a script may have replaced `this` with something that isn't a map
+		if !state.is_map() {
+			*state = Dynamic::from_map(Map::new());
+		}
+		if let Some(mut map) = state.write_lock::<Map>() {
+			self.frame_variables(params, |name, value| { map.insert(name.into(), value); });
+		}
+
+		if script.entry_points.setup {
+			self.call_entry_point(ast, "setup", &mut state)?;
+		}
+
+		Ok(())
+	}
+
+	/// Calls `setup()` or `map()` with `this` bound to `state`. What they return is ignored, they
+	/// hand everything on through `this`.
+	fn call_entry_point(&self, ast: &AST, name: &str, state: &mut Dynamic) -> Result<(), String> {
+		let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(state);
+		self.with_script_context(|| RHAI_ENGINE.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), ast, name, ()))
+			.map(|_| ())
+			.map_err(|e| format!("{e}"))
+	}
+
+	/// Runs `f` with this processor's generator and bin spacing behind the script functions that
+	/// need them, since those are registered once on the shared engine.
+	fn with_script_context<R>(&self, f: impl FnOnce() -> R) -> R {
+		let bin_width = self.sample_rate / self.fft_size as f32;
+		crate::bins::with_bin_width(bin_width, || crate::rng::with_rng(&self.rng, f))
+	}
+
+	/// The variables that stay the same for a whole frame.
+	fn frame_variables(&self, params: &InputParams, mut set: impl FnMut(&'static str, Dynamic)) {
+		set("a", Dynamic::from(params.daw_values[0]));
+		set("b", Dynamic::from(params.daw_values[1]));
+		set("c", Dynamic::from(params.daw_values[2]));
+		set("d", Dynamic::from(params.daw_values[3]));
+
+		// integers, so they compare against script literals
+		set("sound_channel_id", Dynamic::from(params.current_track_id as i64));
+		// the check in `update_mapping` runs without any channels, and should still see one
+		set("num_channels", Dynamic::from(params.channel_count.max(1) as i64));
+		set("mid_side", Dynamic::from(params.mid_side));
+		set("bpm", Dynamic::from(params.bpm));
+		set("playing", Dynamic::from(params.playing));
+		set("looping", Dynamic::from(params.looping));
+		set("beat", Dynamic::from(params.beat));
+		set("bar_phase", Dynamic::from(params.bar_phase));
+		set("daw_time", Dynamic::from(params.daw_time));
+		set("sys_time", Dynamic::from(params.sys_time));
+		set("window_size", Dynamic::from(params.window_size as i64));
+		set("window_factor", Dynamic::from(self.window_factor));
+		set("sample_rate", Dynamic::from(params.sample_rate));
+		set("seed", Dynamic::from(params.seed as i64));
+		set("sidechain", Dynamic::from(params.sidechain));
+		set("note_freq", Dynamic::from(params.note_freq));
+		set("note_vel", Dynamic::from(params.note_vel));
+		set("scale_root", Dynamic::from(params.scale_root as i64));
+		set("scale", Dynamic::from(params.scale as i64));
+	}
+
+	/// The variables that differ from bin to bin, for bin `k`.
+	fn bin_variables(&self, params: &InputParams, k: usize, frequency: f32, magnitude: f32) -> [(&'static str, Dynamic); 11] {
+		let sustain = if params.magnitude_in_db { to_db(self.sustain[k]) } else { self.sustain[k] };
+		[
+			("frequency", Dynamic::from(frequency)),
+			("magnitude", Dynamic::from(magnitude)),
+			("bin", Dynamic::from(k as i64)),
+			("phase", Dynamic::from(self.temp_buffer[k].arg())),
+			("prev_phase", Dynamic::from(self.prev_analysis_phase[k])),
+			("prev_magnitude", Dynamic::from(self.prev_magnitude[k])),
+			("sustain", Dynamic::from(sustain)),
+			("bin_gain", Dynamic::from(1.0_f32)),
+			("gain_db", Dynamic::from(0.0_f32)),
+			("pan", Dynamic::from(0.0_f32)),
+			("range_mode", Dynamic::from(params.range_mode as i64)),
+		]
+	}
+
+	/// Where the mapping sends a bin at each of `frequencies`, with `pitch_shift`, `freq_shift` and
+	/// `range_mode` applied, or `None` where it's dropped or a script fails. Nothing is processed and
+	/// the scripts see a magnitude of 1, so a processor of the editor's own can answer this off the
+	/// audio thread for the tuning readout.
+	pub fn probe_mapping(&self, params: &InputParams, frequencies: &[f32]) -> Vec<Option<f32>> {
+		let bin_width = self.sample_rate / self.fft_size as f32;
+		let pitch_ratio = 2.0_f32.powf(params.pitch_shift / 12.0);
+		let setup = std::iter::once(&self.script).chain(&self.band_scripts)
+			.try_for_each(|script| self.setup_script(script, params));
+		let processed = processed_range(params.min_freq, params.max_freq);
+		frequencies.iter().map(|&frequency| {
+			setup.as_ref().ok()?;
+			let k = ((frequency / bin_width).round() as usize).clamp(1, self.fft_size / 2);
+			if !processed.contains(&self.bin_frequencies[k]) {
+				return (params.out_of_band == OutOfBand::Pass).then_some(frequency);
+			}
+			let output = self.frequency_mapper(self.script_for(params, k), params, k, frequency, 1.0).ok()?;
+			let mapped = output.frequency * pitch_ratio + params.freq_shift;
+			output.range_mode.apply(mapped, bin_width, self.sample_rate / 2.0)
+		}).collect()
+	}
+
+	/// Runs the scripts over every bin but DC, at its center frequency and a magnitude of 1, the way
+	/// they'd map a frame with `params`, so what a script does can be read off without any audio.
+	/// The frequencies are the ones the scripts wrote, before `pitch_shift`, `freq_shift` and
+	/// `range_mode`. Stops at the first error a script runs into.
+	pub fn map_bins(&self, params: &InputParams) -> Result<Vec<BinMapping>, String> {
+		for script in std::iter::once(&self.script).chain(&self.band_scripts) {
+			self.setup_script(script, params)?;
+		}
+		(1..=self.fft_size / 2).map(|k| {
+			let frequency = self.bin_frequencies[k];
+			let output = self.frequency_mapper(self.script_for(params, k), params, k, frequency, 1.0)?;
+			Ok(BinMapping {
+				bin: k,
+				input_frequency: frequency,
+				output_frequency: output.frequency,
+				output_magnitude: output.magnitude * output.bin_gain.max(0.0) * from_db(output.gain_db),
+			})
+		}).collect()
+	}
+
+	/// The name and Rhai type of every variable a script sees, in the order they're set, which
+	/// [`crate::script_api::VARIABLES`] has to match. Allocates, so keep it off the audio thread.
+	pub fn script_variables(&self) -> Vec<(&'static str, &'static str)> {
+		let params = InputParams::default();
+		let mut variables = Vec::new();
+		self.frame_variables(&params, |name, value| variables.push((name, value.type_name())));
+		for (name, value) in self.bin_variables(&params, 0, 0.0, 0.0) {
+			variables.push((name, value.type_name()));
+		}
+		variables
+	}
+
+	/// Runs the script for bin `k`. `prev_phase` and `prev_magnitude` it sees are that bin's
+	/// values from the last processed hop, so they must not be updated before this is called.
 	fn frequency_mapper(
 		&self, 
+		script: &Script,
 		params: &InputParams,
+		k: usize,
 		frequency: f32, 
 		magnitude: f32
-	) -> Result<(f32, f32), String> {
-		let ast = if let Some(ast) = &self.map_ast {
+	) -> Result<ScriptOutput, String> {
+		let ast = if let Some(ast) = &script.ast {
 			ast
 		}else {
-			return Ok((frequency, magnitude))
+			return Ok(ScriptOutput::unchanged(params, frequency, magnitude))
 		};
 
-		let mut scope = Scope::new();
-		scope.push("a", params.daw_values[0]);
-		scope.push("b", params.daw_values[1]);
-		scope.push("c", params.daw_values[2]);
-		scope.push("d", params.daw_values[3]);
-
-		scope.push("sound_channel_id", params.current_track_id as i32);
-		scope.push("bpm", params.bpm);
-		scope.push("daw_time", params.daw_time);
-		scope.push("sys_time", params.sys_time);
-		scope.push("window_size", params.window_size as i32);
-		scope.push("sample_rate", params.sample_rate);
-
-		scope.push("frequency", frequency);
-		scope.push("magnitude", magnitude);
-
-		RHAI_ENGINE.run_ast_with_scope(&mut scope, ast).map_err(|e| format!("{e}"))?;
-		
-		let (frequency, magnitude) = (
-			scope.remove("frequency").unwrap_or(frequency), 
-			scope.remove("magnitude").unwrap_or(magnitude)
+		let magnitude = if params.magnitude_in_db { to_db(magnitude) } else { magnitude };
+		let bin_variables = self.bin_variables(params, k, frequency, magnitude);
+
+		if script.entry_points.map {
+			let mut state = script.state.borrow_mut();
+			if let Some(mut map) = state.write_lock::<Map>() {
+				for (name, value) in bin_variables {
+					map.insert(name.into(), value);
+				}
+			}
+			self.call_entry_point(ast, "map", &mut state)?;
+			let map = state.read_lock::<Map>();
+			Ok(self.script_output(params, frequency, magnitude, |name| map.as_ref()?.get(name).cloned()))
+		}else {
+			let mut scope = Scope::new();
+			self.frame_variables(params, |name, value| { scope.push_dynamic(name, value); });
+			for (name, value) in bin_variables {
+				scope.push_dynamic(name, value);
+			}
+			self.with_script_context(|| RHAI_ENGINE.run_ast_with_scope(&mut scope, ast)).map_err(|e| format!("{e}"))?;
+			Ok(self.script_output(params, frequency, magnitude, |name| scope.get(name).cloned()))
+		}
+	}
+
+	/// Reads back what the script left in its variables; anything missing or of the wrong type
+	/// keeps the value it went in with.
+	fn script_output(&self, params: &InputParams, frequency: f32, magnitude: f32, get: impl Fn(&str) -> Option<Dynamic>) -> ScriptOutput {
+		let float = |name: &str| get(name).and_then(|value| value.try_cast::<f32>());
+		let (frequency, magnitude, bin_gain, pan) = (
+			float("frequency").unwrap_or(frequency), 
+			float("magnitude").unwrap_or(magnitude),
+			float("bin_gain").unwrap_or(1.0_f32),
+			float("pan").unwrap_or(0.0_f32)
 		);
+		let magnitude = if params.magnitude_in_db { from_db(magnitude) } else { magnitude };
+		// NaN would silence the whole frame once it reached the inverse FFT
+		let gain_db = float("gain_db").filter(|gain_db| !gain_db.is_nan()).unwrap_or(0.0).clamp(DB_FLOOR, MAX_GAIN_DB);
+		// a number that isn't a mode keeps the param's
+		let range_mode = get("range_mode")
+			.and_then(|mode| mode.try_cast::<i64>())
+			.and_then(|mode| usize::try_from(mode).ok())
+			.and_then(|mode| RangeMode::ALL.get(mode).copied())
+			.unwrap_or(params.range_mode);
 
-		Ok((frequency, magnitude))
+		if params.script_window && let Some(window_factor) = float("window_factor") {
+			self.requested_window_factor.set(Some(window_factor.clamp(0.0, 1.0)));
+		}
+
+		ScriptOutput { frequency, magnitude, bin_gain, gain_db, pan, range_mode }
 	}
 
 	pub fn renew_window_size(&mut self, window_size: usize) -> Option<usize> {
-		let window_size = window_size.next_power_of_two();
-		let window_size = window_size.max(OVERLAP_RATIO);
-
+		let window_size = self.round_window_size(window_size);
 		if window_size == self.window_size {
 			return None;
 		}
 
 		self.window_size = window_size;
-		self.frame_hop = window_size / OVERLAP_RATIO;
+		self.frame_hop = window_size / self.overlap;
+
+		self.input_buffer.set_capacity(window_size);
+		self.output_buffer.set_capacity(window_size);
 
-		self.input_buffer = RingBuffer::new(window_size);
-		self.output_buffer = RingBuffer::new(window_size);
+		self.update_fft_size();
+		self.reset();
 
-		self.bin_frequencies = (0..window_size).map(|k| k as f32 * self.sample_rate / window_size as f32).collect();
+		Some(window_size)
+	}
 
-		let mut planner = FftPlanner::new();
-		self.fft = planner.plan_fft_forward(window_size);
-		self.ifft = planner.plan_fft_inverse(window_size);
+	/// Pads every frame with zeros to `factor` times the window before the FFT, for bins that many
+	/// times closer together without a longer window or more latency. The FFT is capped at
+	/// [`MAX_WINDOW_SIZE`], so the longest windows get less or none. Returns whether it changed.
+	pub fn renew_zero_pad(&mut self, factor: usize) -> bool {
+		let factor = round_zero_pad(factor);
+		if factor == self.zero_pad {
+			return false;
+		}
 
-		self.prev_analysis_phase = vec![0.0; window_size];
-		// self.prev_synthesis_phase = vec![0.0; window_size];
+		self.zero_pad = factor;
+		self.update_fft_size();
+		self.reset();
 
-		self.temp_buffer = vec![Complex::ZERO; window_size];
-		self.output_temp_buffer = vec![Complex::ZERO; window_size];
+		true
+	}
+
+	/// Switches the low latency mode on or off, which starts over like a new window size. Returns
+	/// whether it changed.
+	pub fn renew_low_latency(&mut self, low_latency: bool) -> bool {
+		if low_latency == self.low_latency {
+			return false;
+		}
+
+		self.low_latency = low_latency;
+		self.reset();
+
+		true
+	}
+
+	fn round_window_size(&self, window_size: usize) -> usize {
+		window_size.next_power_of_two().clamp(self.overlap, MAX_WINDOW_SIZE)
+	}
+
+	/// Whether `input_params` ask for a different window size, padding or latency mode than the
+	/// one running.
+	fn layout_changes(&self, input_params: &InputParams) -> bool {
+		self.round_window_size(input_params.window_size) != self.window_size
+			|| round_zero_pad(input_params.zero_pad_factor) != self.zero_pad
+			|| input_params.low_latency != self.low_latency
+	}
+
+	fn update_fft_size(&mut self) {
+		self.fft_size = (self.window_size * self.zero_pad).min(MAX_WINDOW_SIZE);
+		let (fft, ifft) = &self.fft_plans[&self.fft_size];
+		self.fft = fft.clone();
+		self.ifft = ifft.clone();
+		self.update_bin_frequencies();
+		// learned for bins that aren't there anymore
+		self.noise_profile.clear();
+	}
+
+	/// Drops all analysis history and buffered audio, as if the processor had just been created.
+	pub fn reset(&mut self) {
+		self.input_buffer.clear();
+		self.history.clear();
+		self.stretch_lag = 0.0;
+		self.stretch_restart = true;
+		self.output_buffer.clear();
+
+		self.prev_analysis_phase.fill(0.0);
+		self.prev_magnitude.fill(0.0);
+		self.prev_target_freq.fill(f32::NAN);
+		self.prev_synthesis_phase.fill(f64::NAN);
+		self.silent_frames.fill(0);
+		self.sustain.fill(0.0);
+		self.shift_phase = 0.0;
+
+		self.envelope.fill(1.0);
+		self.freeze.clear();
+		// a snapshot's bins only mean anything at the window it was taken with
+		for snapshot in &mut self.snapshots {
+			snapshot.clear();
+		}
+		self.snapshot_requests = [false; 2];
+		self.peak_hold.fill(0.0);
 
 		self.input_count = 0;
-		self.output_count = 0;
+		self.requested_window_factor.set(None);
+		self.script.clear_state();
+		for script in &self.band_scripts {
+			script.clear_state();
+		}
 
-		Some(window_size)
+		self.rng.set(ScriptRng::new(self.seed));
+		self.whisper_rng = ScriptRng::new(!self.seed);
+	}
+
+	/// Restarts the script random sequence from `seed` if it differs from the current one.
+	pub fn renew_seed(&mut self, seed: u64) {
+		if self.seed == seed {
+			return;
+		}
+
+		self.seed = seed;
+		self.rng.set(ScriptRng::new(seed));
+		// a separate stream, so turning whisper on doesn't change what the script draws
+		self.whisper_rng = ScriptRng::new(!seed);
+	}
+
+	/// How far the output has faded over to the dry signal, 0 when processing and 1 when bypassed.
+	pub fn bypass_mix(&self) -> f32 {
+		self.bypass_mix
+	}
+
+	/// Latency in samples between an input sample and its processed output.
+	pub fn latency(&self) -> usize {
+		latency_for(self.window_size, self.overlap, self.low_latency)
+	}
+
+	/// Where in the output buffer the next hop is read from: the start, or further in by as much as
+	/// the latency is cut.
+	fn output_start(&self) -> usize {
+		self.window_size - self.latency()
+	}
+
+	/// Works out `early_gain` for the hop `output_start` reads. Every frame that will overlap there
+	/// adds its squared window; the ones already added get divided into the lot, so a spectrum that
+	/// isn't changed still comes out whole.
+	fn update_early_gain(&mut self, offset: f32) {
+		let squared = |i: usize| window(self.window_size, i, offset, self.window_factor).powi(2);
+		let start = self.output_start();
+		for (j, gain) in self.early_gain[..self.frame_hop].iter_mut().enumerate() {
+			let position = start + j;
+			let added: f32 = (position..self.window_size).step_by(self.frame_hop).map(squared).sum();
+			let total: f32 = (position % self.frame_hop..self.window_size).step_by(self.frame_hop).map(squared).sum();
+			*gain = if added > 0.0 { total / added } else { 0.0 };
+		}
 	}
 
 	pub fn renew_sample_rate(&mut self, sample_rate: f32) {
@@ -212,85 +1251,1164 @@ impl PhaseVocoder {
 		}
 
 		self.sample_rate = sample_rate;
-		self.bin_frequencies = (0..self.window_size).map(|k| k as f32 * self.sample_rate / self.window_size as f32).collect();
+		self.update_bin_frequencies();
 	}
 
 	pub fn process(&mut self, signal: &mut [f32], input_params: &InputParams) {
-		self.renew_window_size(input_params.window_size);
 		self.renew_sample_rate(input_params.sample_rate);
+		self.renew_seed(input_params.seed);
+
+		let bypass_target = if input_params.bypass { 1.0 } else { 0.0 };
+		let bypass_step = 1000.0 / (BYPASS_FADE_MS * self.sample_rate).max(1.0);
+		let resize_step = 1000.0 / (RESIZE_FADE_MS * self.sample_rate).max(1.0);
+		let mut resizing = self.layout_changes(input_params);
+
+		let mut pos = 0;
+		while pos < signal.len() {
+			// switching drops everything buffered, so it waits for the output to have faded out
+			if resizing && self.resize_fade <= 0.0 {
+				self.renew_window_size(input_params.window_size);
+				self.renew_zero_pad(input_params.zero_pad_factor);
+				self.renew_low_latency(input_params.low_latency);
+				resizing = false;
+				self.resize_fade = 1.0;
+				self.input_fade = 0.0;
+			}
+
+			let mut len = self.frame_hop.saturating_sub(self.input_count).max(1).min(signal.len() - pos);
+			// a chunk stops where the fade out does, so the switch lands on the same sample however
+			// the host splits its blocks; rounding down may leave a sample for one more chunk of 1
+			if resizing {
+				len = len.min(((self.resize_fade / resize_step) as usize).max(1));
+			}
+			let chunk = &mut signal[pos..pos + len];
+			pos += len;
+			let latency = self.latency();
+			let output_start = self.output_start();
+			// where in the hop this chunk starts; the hop the output is read from begins at
+			// `output_start` and lies inside the buffer, so reading never wraps around it
+			let offset = self.input_count;
+
+			if self.input_fade < 1.0 {
+				for sample in chunk.iter_mut() {
+					self.input_fade = (self.input_fade + resize_step).min(1.0);
+					*sample *= self.input_fade;
+				}
+			}
+			self.input_buffer.push_slice(chunk);
+			self.history.push_slice(chunk);
+			self.input_count += len;
+			for (i, sample) in chunk.iter_mut().enumerate() {
+				let position = offset + i;
+				let wet = if self.low_latency {
+					self.output_buffer[output_start + position] * self.early_gain[position]
+				}else {
+					self.output_buffer[output_start + position]
+				};
+
+				// the processed signal is `latency` samples late, so the dry one is read from just as
+				// far back, and toggling bypass doesn't move anything in time
+				self.bypass_mix = if self.bypass_mix < bypass_target {
+					(self.bypass_mix + bypass_step).min(bypass_target)
+				}else {
+					(self.bypass_mix - bypass_step).max(bypass_target)
+				};
+				*sample = if self.bypass_mix > 0.0 {
+					let dry = self.history[-((len - i + latency) as isize)];
+					if self.bypass_mix >= 1.0 { dry } else { wet + (dry - wet) * self.bypass_mix }
+				}else {
+					wet
+				};
 
-		for sample in signal.iter_mut() {
-			self.input_buffer.push(*sample);
-			self.input_count += 1;
-			*sample = self.output_buffer[self.output_count] * 4.0;
-			self.output_count = (self.output_count + 1) % self.output_buffer.capacity(); 
+				self.resize_fade = if resizing {
+					(self.resize_fade - resize_step).max(0.0)
+				}else {
+					(self.resize_fade + resize_step).min(1.0)
+				};
+				*sample *= self.resize_fade;
+			}
 			if self.input_count >= self.frame_hop {
 				self.output_buffer.extend_defaults(self.frame_hop);
 				self.input_count -= self.frame_hop;
-				self.output_count = (self.output_count + self.output_buffer.capacity() - self.frame_hop) % self.output_buffer.capacity();
 				self.process_inner(input_params);
 			}
 		}
 	}
 
+	/// Pushes silence through until everything taken in so far has come out, into `tail`, and
+	/// returns how many samples that took: [`PhaseVocoder::latency`], or less if `tail` is shorter.
+	/// Samples past that are left alone. Frozen or stretched sound keeps going after the tail, and
+	/// isn't waited for.
+	pub fn flush(&mut self, tail: &mut [f32], input_params: &InputParams) -> usize {
+		let len = self.latency().min(tail.len());
+		tail[..len].fill(0.0);
+		self.process(&mut tail[..len], input_params);
+		len
+	}
+
+	/// Moves the stretched analysis one frame further behind the live input and returns how many
+	/// input samples lie between this frame and the previous one, or `None` if the analysis had to
+	/// jump (history exhausted, or stretching just turned off) and the phases can't be continued.
+	fn advance_stretch(&mut self, stretch: f32) -> Option<f32> {
+		let old_lag = self.stretch_lag as usize;
+		if stretch > 1.0 {
+			self.stretch_lag += self.frame_hop as f32 * (1.0 - 1.0 / stretch.min(MAX_STRETCH));
+		}else {
+			self.stretch_lag = 0.0;
+		}
+
+		if self.stretch_lag as usize + self.window_size > self.history.capacity() {
+			self.stretch_lag = 0.0;
+		}
+
+		let new_lag = self.stretch_lag as usize;
+		if new_lag < old_lag || std::mem::take(&mut self.stretch_restart) {
+			return None;
+		}
+		Some((self.frame_hop + old_lag - new_lag) as f32)
+	}
+
+	/// Smears the magnitudes of `temp_buffer` over `radius` bins either side, with two passes of a
+	/// moving average that add up to a triangular kernel. Every bin keeps its own analysis phase, so
+	/// a partial's bins stay coherent and only the bins it spreads into sound noisier.
+	fn blur_magnitudes(&mut self, spectrum_size: usize, radius: usize) {
+		for (magnitude, value) in self.blur_magnitude[..spectrum_size].iter_mut().zip(&self.temp_buffer[..spectrum_size]) {
+			*magnitude = value.norm();
+		}
+		moving_average(&self.blur_magnitude[..spectrum_size], &mut self.blur_scratch[..spectrum_size], radius);
+		moving_average(&self.blur_scratch[..spectrum_size], &mut self.blur_magnitude[..spectrum_size], radius);
+		for (value, magnitude) in self.temp_buffer[..spectrum_size].iter_mut().zip(&self.blur_magnitude[..spectrum_size]) {
+			*value = Complex::from_polar(*magnitude, value.arg());
+		}
+	}
+
+	/// Scales `output_temp_buffer` so its energy is that of `temp_buffer`, which keeps the level
+	/// steady when a script piles many bins onto a few or drops most of them. Energy is compared
+	/// after freeze and blur, so those still change the level. DC is passed through unmapped, so it's
+	/// left out of both sides, and a frame with nothing mapped into range stays silent.
+	fn match_energy(&mut self, spectrum_size: usize) {
+		let input_energy: f32 = self.temp_buffer[1..spectrum_size].iter().map(|value| value.norm_sqr()).sum();
+		let output_energy: f32 = self.output_temp_buffer[1..spectrum_size].iter().map(|value| value.norm_sqr()).sum();
+		if output_energy <= f32::MIN_POSITIVE {
+			return;
+		}
+		let scale = (input_energy / output_energy).sqrt();
+		for value in self.output_temp_buffer[1..spectrum_size].iter_mut() {
+			*value *= scale;
+		}
+	}
+
+	/// Sets the frozen magnitudes and how fast each bin turns to `morph` of the way from snapshot A
+	/// to B. The speed is weighted by what each snapshot puts in the bin, so a partial only one of
+	/// them has keeps its frequency instead of being pulled towards whatever leaked into the other's
+	/// bin. Weights differ from bin to bin, so each partial's bins then all take its peak's speed:
+	/// turning apart, they'd drift out of step and cancel as the morph moves.
+	fn morph_snapshots(&mut self, spectrum_size: usize, morph: f32) {
+		let [a, b] = &self.snapshots;
+		for k in 0..spectrum_size {
+			let (weight_a, weight_b) = (a.magnitude[k] * (1.0 - morph), b.magnitude[k] * morph);
+			self.frozen_magnitude[k] = weight_a + weight_b;
+			self.frozen_advance[k] = if weight_a + weight_b > 0.0 {
+				(a.advance[k] * weight_a + b.advance[k] * weight_b) / (weight_a + weight_b)
+			}else {
+				a.advance[k] + (b.advance[k] - a.advance[k]) * morph
+			};
+		}
+
+		find_peaks(&mut self.peaks, spectrum_size, |k| self.frozen_magnitude[k]);
+		for (i, &peak) in self.peaks.iter().enumerate() {
+			let advance = self.frozen_advance[peak];
+			for k in peak_region(&self.peaks, i, spectrum_size) {
+				self.frozen_advance[k] = advance;
+			}
+		}
+	}
+
+	/// How far bin `k` turns per input sample if it's at `value` now: the frequency measured against
+	/// the last frame over `analysis_hop`, or without one to measure against, its center frequency.
+	fn bin_advance(&self, k: usize, value: Complex<f32>, analysis_hop: Option<f32>) -> f32 {
+		let omega = 2.0 * PI * k as f32 / self.fft_size as f32;
+		let deviation = match analysis_hop {
+			Some(hop) if k > 0 => principal_argument(value.arg() - self.prev_analysis_phase[k] - omega * hop) / hop,
+			_ => 0.0,
+		};
+		omega + deviation
+	}
+
+	/// Crossfades `temp_buffer` towards the frozen spectrum over `FREEZE_FADE_MS`. Frozen bins keep
+	/// turning at the frequency they had when captured, measured over `analysis_hop`, so everything
+	/// after this sees a steady sound rather than a stuck one. A capture takes what was heard at
+	/// that frame, old freeze included. Must run before `prev_analysis_phase` is updated for this frame.
+	///
+	/// With both snapshots taken, the frozen spectrum is a morph between them instead, worked out
+	/// afresh every frame so `morph` can move while held. Magnitudes and how fast each bin turns are
+	/// interpolated, and the phase keeps turning at the interpolated speed from where it was, so the
+	/// morph never jumps: interpolating the phases themselves would cancel bins out halfway.
+	fn apply_freeze(&mut self, spectrum_size: usize, held: bool, analysis_hop: Option<f32>, morph: f32) {
+		for slot in MorphSlot::ALL {
+			if std::mem::take(&mut self.snapshot_requests[slot as usize]) {
+				for k in 0..spectrum_size {
+					let value = self.temp_buffer[k];
+					let advance = self.bin_advance(k, value, analysis_hop);
+					let snapshot = &mut self.snapshots[slot as usize];
+					snapshot.magnitude[k] = value.norm();
+					snapshot.phase[k] = value.arg();
+					snapshot.advance[k] = advance;
+				}
+				self.snapshots[slot as usize].set_captured();
+			}
+		}
+
+		self.freeze.set_held(held);
+		let old_mix = self.freeze.mix();
+		let step = self.frame_hop as f32 * 1000.0 / (FREEZE_FADE_MS * self.sample_rate).max(1.0);
+		let capture = self.freeze.next_frame(step);
+		let mix = self.freeze.mix();
+		if !capture && mix <= 0.0 {
+			return;
+		}
+
+		let hop = analysis_hop.unwrap_or(self.frame_hop as f32);
+		let morph = morph.clamp(0.0, 1.0);
+		let morphing = self.snapshots.iter().all(Snapshot::captured);
+		if morphing {
+			self.morph_snapshots(spectrum_size, morph);
+		}
+		for k in 0..spectrum_size {
+			let live = self.temp_buffer[k];
+			self.frozen_phase[k] = principal_argument(self.frozen_phase[k] + self.frozen_advance[k] * hop);
+			let frozen = Complex::from_polar(self.frozen_magnitude[k], self.frozen_phase[k]);
+			let frozen = if capture && morphing {
+				// each bin starts from the phase of the snapshot that puts more in it, which keeps the
+				// bins of each partial in step, and either end hold that snapshot as it was taken
+				let [a, b] = &self.snapshots;
+				let louder = if a.magnitude[k] * (1.0 - morph) >= b.magnitude[k] * morph { a } else { b };
+				self.frozen_phase[k] = louder.phase[k];
+				Complex::from_polar(self.frozen_magnitude[k], self.frozen_phase[k])
+			}else if capture {
+				let heard = live + (frozen - live) * old_mix;
+				self.frozen_magnitude[k] = heard.norm();
+				self.frozen_phase[k] = heard.arg();
+				self.frozen_advance[k] = self.bin_advance(k, heard, analysis_hop);
+				heard
+			}else {
+				frozen
+			};
+			self.temp_buffer[k] = live + (frozen - live) * mix;
+		}
+	}
+
+	/// Holds every bin at the loudest it's been, falling by 60 dB over `hold_time` seconds, so short
+	/// sounds ring on after they stop. A bin that's fallen below its hold keeps turning at the
+	/// frequency it had when it was last at its loudest, the way a frozen bin does. Bins reach their
+	/// loudest at different frames, so each partial's bins then all take its peak's speed, or they'd
+	/// beat against each other as they fall. Must run before `prev_analysis_phase` is updated for
+	/// this frame.
+	fn apply_hold(&mut self, spectrum_size: usize, hold_time: f32, analysis_hop: Option<f32>) {
+		if hold_time <= 0.0 {
+			// so turning it back on doesn't bring back what was held before
+			self.peak_hold[..spectrum_size].fill(0.0);
+			return;
+		}
+
+		let decay = from_db(-60.0 * self.frame_hop as f32 / (hold_time * self.sample_rate));
+		let hop = analysis_hop.unwrap_or(self.frame_hop as f32);
+		for k in 0..spectrum_size {
+			let live = self.temp_buffer[k];
+			let held = self.peak_hold[k] * decay;
+			if live.norm() >= held {
+				self.peak_hold[k] = live.norm();
+				self.hold_phase[k] = live.arg();
+				self.hold_advance[k] = self.bin_advance(k, live, analysis_hop);
+			}else {
+				self.peak_hold[k] = held;
+				self.hold_phase[k] = principal_argument(self.hold_phase[k] + self.hold_advance[k] * hop);
+				self.temp_buffer[k] = Complex::from_polar(held, self.hold_phase[k]);
+			}
+		}
+
+		find_peaks(&mut self.peaks, spectrum_size, |k| self.peak_hold[k]);
+		for (i, &peak) in self.peaks.iter().enumerate() {
+			let advance = self.hold_advance[peak];
+			for k in peak_region(&self.peaks, i, spectrum_size) {
+				self.hold_advance[k] = advance;
+			}
+		}
+	}
+
+	/// Puts every bin that has been silent for a whole window back the way [`PhaseVocoder::reset`]
+	/// leaves it. The phases carried from frame to frame mean nothing once there's no sound behind
+	/// them, and a bin coming back after a gap then starts out like it would in a fresh processor.
+	/// Waiting out a window keeps quiet passages that only dip below the threshold from being cut up.
+	fn forget_silent_bins(&mut self, spectrum_size: usize) {
+		// `from_db` takes the floor itself for silence
+		let threshold = 10.0_f32.powf(DB_FLOOR / 20.0) / self.level_scale();
+		for k in 1..spectrum_size {
+			if self.temp_buffer[k].norm() >= threshold {
+				self.silent_frames[k] = 0;
+				continue;
+			}
+
+			self.silent_frames[k] = (self.silent_frames[k] + 1).min(self.overlap);
+			if self.silent_frames[k] == self.overlap {
+				self.prev_analysis_phase[k] = 0.0;
+				self.prev_magnitude[k] = 0.0;
+				self.prev_target_freq[k] = f32::NAN;
+				self.prev_synthesis_phase[k] = f64::NAN;
+			}
+		}
+	}
+
+	/// Widens a mono source: the first channel of a pair adds `amount` of every output bin turned
+	/// by a quarter turn, and the second takes the same away, in a direction drawn from the seed for
+	/// every few bins. Each side's bins keep their magnitude apart from a flat `sqrt(1 + amount²)`, so
+	/// neither side is comb filtered, and the sum of the two is the unwidened output twice over.
+	/// Their correlation falls to `(1 - amount²) / (1 + amount²)`, 0 at full amount. DC and Nyquist
+	/// only hold a real value and are left alone.
+	fn decorrelate(&mut self, spectrum_size: usize, params: &InputParams) {
+		let amount = if params.current_track_id == 0 { params.decorrelation } else { -params.decorrelation };
+		let group = DECORRELATION_GROUP * self.fft_size / self.window_size;
+		for (k, value) in self.output_temp_buffer[..spectrum_size - 1].iter_mut().enumerate().skip(1) {
+			// the same in both channels' processors, and for as long as the seed stays
+			let draw = ScriptRng::new(self.seed.rotate_left(32) ^ (k / group) as u64).next_u64();
+			let direction = if draw & 1 == 1 { 1.0 } else { -1.0 };
+			*value *= Complex::new(1.0, amount * direction);
+		}
+	}
+
+	/// The sample the window peaks at, with the window turned by `offset`.
+	fn window_center(&self, offset: f32) -> usize {
+		((0.5 - offset).rem_euclid(1.0) * self.window_size as f32).round() as usize
+	}
+
+	/// Gives every output bin the phase of a single pulse where the window peaks, keeping its
+	/// magnitude. Each frame then comes out as the same buzz, whatever the bins were mapped to, and
+	/// the frames repeating every hop are heard as a monotone at the frame rate.
+	fn robotize(&mut self, spectrum_size: usize, offset: f32) {
+		let center = self.window_center(offset);
+		for (k, value) in self.output_temp_buffer[..spectrum_size].iter_mut().enumerate() {
+			// whole turns taken out in integers, where the phase doesn't lose precision at high bins
+			let turn = (k * center % self.fft_size) as f32 / self.fft_size as f32;
+			*value = Complex::from_polar(value.norm(), -2.0 * PI * turn);
+		}
+	}
+
+	/// Adds `harmonics` overtones of the strongest peak below `EXCITER_MAX_FUNDAMENTAL` to the output.
+	/// Each is a copy of the fundamental's main lobe, `rolloff` dB per octave quieter, moved up by
+	/// the nearest whole number of bins. Its phases advance from frame to frame at the exact multiple
+	/// of the fundamental, which is what sets the pitch, and keep the overtone locked to it.
+	/// Overtones past Nyquist are dropped.
+	fn add_harmonics(&mut self, spectrum_size: usize, harmonics: usize, rolloff: f32) {
+		// the copied lobe overlap-adds coherently, the same as the fundamental left in place by the
+		// main path, so `rolloff` is relative to the fundamental as it is
+		let bin_width = self.sample_rate / self.fft_size as f32;
+		let max_bin = ((EXCITER_MAX_FUNDAMENTAL / bin_width) as usize).min(spectrum_size.saturating_sub(2));
+		let magnitude = |k: usize| self.temp_buffer[k].norm();
+		let Some(peak) = (1..=max_bin)
+			.filter(|&k| magnitude(k) > magnitude(k - 1) && magnitude(k) >= magnitude(k + 1))
+			.max_by(|&a, &b| magnitude(a).total_cmp(&magnitude(b)))
+		else {
+			return;
+		};
+
+		let fundamental = self.peak_frequency(peak, spectrum_size);
+		let fundamental_phase = self.temp_buffer[peak].arg();
+		// padding spreads the lobe over more bins
+		let lobe_width = EXCITER_LOBE * self.fft_size / self.window_size;
+		let lobe = peak.saturating_sub(lobe_width).max(1)..=(peak + lobe_width).min(spectrum_size - 1);
+		for harmonic in 2..=harmonics + 1 {
+			let gain = from_db(-rolloff * (harmonic as f32).log2());
+			let multiple = (harmonic - 1) as f32;
+			let shift = (fundamental * multiple / bin_width).round() as usize;
+			for k in lobe.clone() {
+				let Some(target) = self.output_temp_buffer[..spectrum_size].get_mut(k + shift) else {
+					break;
+				};
+
+				let value = self.temp_buffer[k];
+				*target += Complex::from_polar(value.norm() * gain, value.arg() + fundamental_phase * multiple);
+			}
+		}
+	}
+
+	/// Turns a bin's magnitude into the level the spectrum view shows, where a full scale sine
+	/// reads about 1.0 with the default window.
+	fn level_scale(&self) -> f32 {
+		8.0 / self.window_size as f32
+	}
+
+	/// Moves every bin's `sustain` towards its magnitude, at the attack time while it's below and the
+	/// release time while it's above. A steady sound ends up at its magnitude, while a click is gone
+	/// before `sustain` has risen far.
+	fn follow_sustain(&mut self, spectrum_size: usize, params: &InputParams) {
+		// per frame, which is a hop of input
+		let coefficient = |ms: f32| (-1000.0 * self.frame_hop as f32 / (ms * self.sample_rate)).exp();
+		let (attack, release) = (coefficient(params.sustain_attack), coefficient(params.sustain_release));
+		for (sustain, value) in self.sustain[..spectrum_size].iter_mut().zip(&self.temp_buffer) {
+			let magnitude = value.norm();
+			let coefficient = if magnitude > *sustain { attack } else { release };
+			*sustain = magnitude + (*sustain - magnitude) * coefficient;
+		}
+	}
+
+	/// Half-wave rectified spectral flux against the previous frame, relative to this frame's
+	/// total magnitude: close to 0 for a steady sound and close to 1 for an attack out of silence.
+	/// Must run before `prev_magnitude` is updated for this frame.
+	fn spectral_flux(&self, spectrum_size: usize) -> f32 {
+		let (rise, total) = self.temp_buffer[1..spectrum_size].iter()
+			.zip(&self.prev_magnitude[1..spectrum_size])
+			.fold((0.0, 0.0), |(rise, total), (value, prev)| {
+				let magnitude = value.norm();
+				(rise + (magnitude - prev).max(0.0), total + magnitude)
+			});
+
+		if total > MAGNITUDE_FLOOR * spectrum_size as f32 { rise / total } else { 0.0 }
+	}
+
+	/// Refines the frequency of bin `k` with a parabola through its log-magnitude and its neighbours'
+	/// when it's a local peak of `temp_buffer`; every other bin keeps its center frequency.
+	fn peak_frequency(&self, k: usize, spectrum_size: usize) -> f32 {
+		let bin_center_freq = self.bin_frequencies[k];
+		if k == 0 || k + 1 >= spectrum_size {
+			return bin_center_freq;
+		}
+
+		let alpha = self.temp_buffer[k - 1].norm().max(MAGNITUDE_FLOOR).ln();
+		let beta = self.temp_buffer[k].norm().max(MAGNITUDE_FLOOR).ln();
+		let gamma = self.temp_buffer[k + 1].norm().max(MAGNITUDE_FLOOR).ln();
+		let denominator = alpha - 2.0 * beta + gamma;
+		if beta <= alpha || beta < gamma || denominator >= 0.0 {
+			return bin_center_freq;
+		}
+
+		let offset = 0.5 * (alpha - gamma) / denominator;
+		(k as f32 + offset) * self.sample_rate / self.fft_size as f32
+	}
+
+	/// The phase bin `k`, at `phase` now, comes out with when it's moved to `ratio` times its own
+	/// frequency: the phase it last came out with, turned on by a hop at its true frequency times
+	/// `ratio`. The true frequency is measured against the last frame over `analysis_hop`, so the
+	/// bins of one partial all turn alike wherever they land, and at the partial's moved pitch.
+	/// A bin with nothing to turn on from starts from `phase`, as after a reset. Must run before
+	/// `prev_analysis_phase` is updated for this frame.
+	fn synthesis_phase(&self, k: usize, phase: f32, analysis_hop: f32, ratio: f32) -> f32 {
+		if self.prev_synthesis_phase[k].is_nan() {
+			return phase;
+		}
+		// in f64 and wrapped: the advance per hop runs to hundreds of radians, and an f32
+		// accumulator left to grow over a long stretch ends up too coarse to hold the pitch
+		let analysis_hop = analysis_hop as f64;
+		let omega = 2.0 * std::f64::consts::PI * k as f64 / self.fft_size as f64;
+		let deviation = principal_argument_f64(phase as f64 - self.prev_analysis_phase[k] as f64 - omega * analysis_hop);
+		principal_argument_f64(
+			self.prev_synthesis_phase[k] + (omega + deviation / analysis_hop) * ratio as f64 * self.frame_hop as f64
+		) as f32
+	}
+
+	/// Identity phase locking (Laroche and Dolson): every local magnitude peak keeps the synthesis
+	/// phase it was given in `locked_phase`, moved or not, and every other bin in its region of
+	/// influence takes the peak's phase plus its own analysis phase offset from the peak, so the
+	/// bins of one partial stay coherent. Regions split halfway between neighbouring peaks.
+	///
+	/// A moved peak takes its whole region along by as many bins as it moved, which keeps the
+	/// partial's shape, and so its level whatever the window, as long as the fraction of a bin is
+	/// moved through a kernel at least `LOCKED_SPREAD_RADIUS` wide. While stretching, bins
+	/// accumulate on their own, so without the locking a partial's bins would never line up again.
+	fn lock_phases(&mut self, spectrum_size: usize) {
+		find_peaks(&mut self.peaks, spectrum_size, |k| self.temp_buffer[k].norm());
+		for (i, &peak) in self.peaks.iter().enumerate() {
+			let peak_phase = self.locked_phase[peak];
+			let peak_analysis_phase = self.temp_buffer[peak].arg();
+			// a peak passed on from outside the processed range or dropped leaves its bins where the
+			// mapping put them
+			let target = self.synthesis[peak];
+			let shift = (target.mapped && target.magnitude > 0.0).then_some(target.index - peak as f32);
+			for k in peak_region(&self.peaks, i, spectrum_size) {
+				self.locked_phase[k] = peak_phase + self.temp_buffer[k].arg() - peak_analysis_phase;
+				let bin = &mut self.synthesis[k];
+				if let Some(shift) = shift && bin.mapped {
+					let index = k as f32 + shift;
+					if index < 0.0 || index >= spectrum_size as f32 {
+						bin.magnitude = 0.0;
+					}
+					bin.index = index;
+				}
+			}
+		}
+	}
+
+	/// Estimates the spectral envelope of `temp_buffer` through the real cepstrum:
+	/// the log-magnitude is transformed back, only the low quefrencies are kept,
+	/// and the result is transformed forward again into `envelope`.
+	fn estimate_envelope(&mut self) {
+		let fft_size = self.fft_size;
+		let spectrum_size = fft_size / 2 + 1;
+		// quefrencies are in samples whatever the padding, so the cut follows the window
+		let lifter = (self.window_size / CEPSTRUM_LIFTER_RATIO).max(1);
+
+		for (value, cepstrum) in self.temp_buffer[..spectrum_size].iter().zip(self.cepstrum_spectrum.iter_mut()) {
+			*cepstrum = Complex::new(value.norm().max(MAGNITUDE_FLOOR).ln(), 0.0);
+		}
+
+		let _ = self.ifft.process_with_scratch(
+			&mut self.cepstrum_spectrum[..spectrum_size], 
+			&mut self.cepstrum_buffer[..fft_size], 
+			&mut self.fft_scratch
+		);
+
+		for (i, value) in self.cepstrum_buffer[..fft_size].iter_mut().enumerate() {
+			if i > lifter && i < fft_size - lifter {
+				*value = 0.0;
+			}
+		}
+
+		let _ = self.fft.process_with_scratch(
+			&mut self.cepstrum_buffer[..fft_size], 
+			&mut self.cepstrum_spectrum[..spectrum_size], 
+			&mut self.fft_scratch
+		);
+
+		for (envelope, value) in self.envelope.iter_mut().zip(self.cepstrum_spectrum[..spectrum_size].iter()) {
+			*envelope = (value.re / fft_size as f32).exp();
+		}
+	}
+
 	fn process_inner(&mut self, input_params: &InputParams) {
-		for (i, value) in self.temp_buffer.iter_mut().enumerate() {
-			*value = Complex::new(
-				window(self.window_size, i, input_params.window_offset, input_params.window_factor) * self.input_buffer[i], 
-				0.0
-			);
-			self.output_temp_buffer[i] = Complex::ZERO;
+		let spectrum_size = self.fft_size / 2 + 1;
+
+		// a factor written by the script during the previous frame wins, if scripts may write it
+		self.window_factor = match self.requested_window_factor.get() {
+			Some(window_factor) if input_params.script_window => window_factor,
+			_ => input_params.window_factor,
+		};
+		let ola_window = (self.window_size, input_params.window_offset, self.window_factor, self.low_latency);
+		if ola_window != self.ola_window {
+			self.ola = overlap_add(self.window_size, self.overlap, input_params.window_offset, self.window_factor);
+			if self.low_latency {
+				self.update_early_gain(input_params.window_offset);
+			}
+			self.ola_window = ola_window;
+		}
+
+		let stretching = input_params.stretch > 1.0;
+		let analysis_hop = self.advance_stretch(input_params.stretch);
+		if stretching {
+			let lag = self.stretch_lag as usize;
+			for (i, value) in self.frame_buffer[..self.window_size].iter_mut().enumerate() {
+				*value = self.history[-((lag + self.window_size - i) as isize)];
+			}
+		}else {
+			self.input_buffer.copy_into(&mut self.frame_buffer[..self.window_size]);
+		}
+		for (i, value) in self.frame_buffer[..self.window_size].iter_mut().enumerate() {
+			*value *= window(self.window_size, i, input_params.window_offset, self.window_factor);
+		}
+		self.frame_buffer[self.window_size..self.fft_size].fill(0.0);
+		self.output_temp_buffer.fill(Complex::ZERO);
+
+		let _ = self.fft.process_with_scratch(
+			&mut self.frame_buffer[..self.fft_size], 
+			&mut self.temp_buffer[..spectrum_size], 
+			&mut self.fft_scratch
+		);
+
+		// the noise comes off before anything else, so a freeze holds the cleaned up sound
+		self.noise_profile.learn(&self.temp_buffer[..spectrum_size], input_params.learn_noise);
+		self.noise_profile.subtract(&mut self.temp_buffer[..spectrum_size], input_params.denoise);
+
+		// before the freeze, which then catches whatever is being held
+		self.apply_hold(spectrum_size, input_params.hold_time, analysis_hop);
+		self.apply_freeze(spectrum_size, input_params.freeze, analysis_hop, input_params.morph);
+
+		// `blur` is in Hz, so it sounds the same at every window size
+		let blur_radius = (input_params.blur * self.fft_size as f32 / self.sample_rate).round() as usize;
+		if blur_radius > 0 {
+			self.blur_magnitudes(spectrum_size, blur_radius);
 		}
 
-		self.fft.process(&mut self.temp_buffer);
+		let pitch_ratio = 2.0_f32.powf(input_params.pitch_shift / 12.0);
+		// moving a sound up by `freq_shift` Hz also turns every bin by that much per second; the bins
+		// keep their own phase advance, so without this they'd cancel out from frame to frame.
+		// The bins themselves move by whole bins only, and the turning makes up the rest exactly
+		self.shift_phase = principal_argument(
+			self.shift_phase + 2.0 * PI * input_params.freq_shift * self.frame_hop as f32 / self.sample_rate
+		);
+		let bin_width = self.sample_rate / self.fft_size as f32;
+		let shift_error = (input_params.freq_shift / bin_width).round() * bin_width - input_params.freq_shift;
 
-		for (k, value) in self.temp_buffer.iter().enumerate().take(self.window_size / 2 + 1) {
+		if input_params.preserve_formants {
+			self.estimate_envelope();
+		}
+
+		// on an onset the phases are passed through as analysed, which keeps the attack's shape
+		// instead of smearing it over the window
+		let transient = input_params.transient_sensitivity > 0.0
+			&& self.spectral_flux(spectrum_size) > 1.0 - input_params.transient_sensitivity;
+		let analysis_hop = if transient { None } else { analysis_hop };
+
+		self.follow_sustain(spectrum_size, input_params);
+		let center = self.window_center(input_params.window_offset);
+		for script in std::iter::once(&self.script).chain(&self.band_scripts) {
+			if let Err(e) = self.setup_script(script, input_params) {
+				self.script_error.get_or_insert(e);
+			}
+		}
+		let processed = processed_range(input_params.min_freq, input_params.max_freq);
+		// for `auto_makeup`: the frame's energy before and after the scripts' `gain_db`
+		let mut energy_before_gain = 0.0;
+		let mut energy_after_gain = 0.0;
+		for (k, value) in self.temp_buffer[..spectrum_size].iter().enumerate() {
 			if k == 0 {
 				self.output_temp_buffer[0] = *value;
 				continue;
 			}
 
-			let magnitude = value.norm();
+			// one-pole smoothing across frames, before the script sees the magnitude
+			let smoothed = input_params.smoothing * self.prev_magnitude[k] + (1.0 - input_params.smoothing) * value.norm();
 			let bin_center_freq = self.bin_frequencies[k];
-			let Ok((mapped_freq, magnitude)) = self.frequency_mapper(input_params, bin_center_freq, magnitude) else { unreachable!() };
 
-			if mapped_freq < 0.0 || mapped_freq >= self.sample_rate / 2.0 {
+			// a bin outside of the processed range costs no script run, and is passed on the way
+			// an identity mapping would leave it, with the state kept up so it can come back in
+			if !processed.contains(&bin_center_freq) {
+				self.locked_phase[k] = match analysis_hop {
+					Some(analysis_hop) if stretching => self.synthesis_phase(k, value.arg(), analysis_hop, 1.0),
+					_ => value.arg(),
+				};
+				let magnitude = if input_params.out_of_band == OutOfBand::Pass { value.norm() } else { 0.0 };
+				self.synthesis[k] = SynthesisBin { magnitude, index: k as f32, mapped: false };
+				self.prev_target_freq[k] = bin_center_freq;
+				self.prev_analysis_phase[k] = value.arg();
+				self.prev_magnitude[k] = smoothed;
 				continue;
 			}
 
-			let new_phase = 
-				self.prev_analysis_phase[k] + 
-				2.0 * PI * bin_center_freq * self.frame_hop as f32 / self.sample_rate;
+			// the interpolated peak is only what the script sees; whether the bin moved at all is
+			// told from what it gave back for it
+			let source_freq = if input_params.peak_interpolation {
+				self.peak_frequency(k, spectrum_size)
+			}else {
+				bin_center_freq
+			};
+			let output = self.frequency_mapper(self.script_for(input_params, k), input_params, k, source_freq, smoothed)
+				.unwrap_or_else(|e| {
+					self.script_error.get_or_insert(e);
+					ScriptOutput::unchanged(input_params, source_freq, smoothed)
+				});
+			// a frequency shift adds the same Hz everywhere, so harmonics stop being multiples of anything
+			let mapped_freq = output.frequency * pitch_ratio + input_params.freq_shift;
+			let magnitude = output.magnitude * output.bin_gain.max(0.0) * pan_gain(input_params, output.pan);
+			energy_before_gain += magnitude * magnitude;
+			let magnitude = magnitude * from_db(output.gain_db);
+			energy_after_gain += magnitude * magnitude;
+			let magnitude = if input_params.spectral_ratio > 1.0 {
+				let level = to_db(magnitude * self.level_scale());
+				let gain = input_params.spectral_dynamics.gain(level, input_params.spectral_threshold, input_params.spectral_ratio);
+				magnitude * from_db(gain + input_params.spectral_makeup)
+			}else {
+				magnitude
+			};
+
+			// `slew` limits how far a bin's target may move per frame, in Hz per second
+			let prev_target_freq = self.prev_target_freq[k];
+			let mapped_freq = if input_params.slew > 0.0 && !prev_target_freq.is_nan() {
+				let max_step = input_params.slew * self.frame_hop as f32 / self.sample_rate;
+				mapped_freq.clamp(prev_target_freq - max_step, prev_target_freq + max_step)
+			}else {
+				mapped_freq
+			};
+			self.prev_target_freq[k] = mapped_freq;
+			let mapped_freq = output.range_mode.apply(mapped_freq, bin_width, self.sample_rate / 2.0);
 
+			// a bin left where it is keeps its analysis phase, so an identity mapping gives back the
+			// input exactly. A moved one turns at its true frequency moved the same way the mapping
+			// moved it, the rest of `freq_shift` being made up by `shift_phase`; turning it at the
+			// frequency of the bin it came from or lands in would put the sound at the wrong pitch
+			let unmoved = mapped_freq == Some(source_freq);
+			self.locked_phase[k] = match (analysis_hop, mapped_freq) {
+				(Some(analysis_hop), Some(mapped_freq)) if stretching || !unmoved => {
+					let ratio = (mapped_freq - input_params.freq_shift) / source_freq;
+					self.synthesis_phase(k, value.arg(), analysis_hop, ratio)
+				},
+				_ => value.arg(),
+			};
+
+			// the script reads the previous frame's values, so they're only replaced once it has run,
+			// and before the range check so a bin mapped out of range doesn't keep a stale phase
 			self.prev_analysis_phase[k] = value.arg();
+			self.prev_magnitude[k] = smoothed;
 
-			let new_idx = mapped_freq / self.sample_rate * self.window_size as f32;
+			let Some(mapped_freq) = mapped_freq else {
+				self.synthesis[k] = SynthesisBin::default();
+				continue;
+			};
+
+			let new_idx = if unmoved { k as f32 } else { ((mapped_freq + shift_error) / bin_width).max(0.0) };
 			let ratio = new_idx.fract();
 			let k_low = new_idx.floor() as usize;
 
-			if k_low <= self.window_size / 2 {
-				self.output_temp_buffer[k_low] += (1.0 - ratio) * Complex::from_polar(magnitude, new_phase);
+			let magnitude = if input_params.preserve_formants {
+				// a bin mapped onto Nyquist has nothing above it to blend with
+				let k_high = (k_low + 1).min(self.fft_size / 2);
+				let envelope_at = (1.0 - ratio) * self.envelope[k_low] + ratio * self.envelope[k_high];
+				magnitude * envelope_at / self.envelope[k]
+			}else {
+				magnitude
+			};
+
+			// `tilt` dB per octave away from the pivot, at the frequency the bin ends up at;
+			// DC has no octave, so it's left alone
+			let magnitude = if input_params.tilt != 0.0 && mapped_freq > 0.0 && input_params.tilt_pivot > 0.0 {
+				magnitude * from_db(input_params.tilt * (mapped_freq / input_params.tilt_pivot).log2())
+			}else {
+				magnitude
+			};
+
+			self.synthesis[k] = SynthesisBin { magnitude, index: new_idx, mapped: true };
+		}
+
+		let locked = input_params.phase_lock || stretching;
+		if locked {
+			self.lock_phases(spectrum_size);
+		}
+		let radius = if locked { (1.0 + input_params.spread).max(LOCKED_SPREAD_RADIUS) } else { 1.0 + input_params.spread };
+
+		for k in 1..spectrum_size {
+			// outside of stretching the accumulator follows the analysis for a bin left in place,
+			// so that stretching picks up with the bins of each partial in phase with each other
+			let phase = self.locked_phase[k];
+			self.prev_synthesis_phase[k] = phase as f64;
+
+			let SynthesisBin { magnitude, index, mapped } = self.synthesis[k];
+			if magnitude == 0.0 {
+				continue;
+			}
+			if !mapped {
+				self.output_temp_buffer[k] += Complex::from_polar(magnitude, phase);
+				continue;
+			}
+
+			let mut phase = phase + self.shift_phase;
+			if input_params.whisper > 0.0 {
+				phase += input_params.whisper * (self.whisper_rng.next_f32() * 2.0 - 1.0) * PI;
+			}
+			let value = Complex::from_polar(magnitude, phase);
+
+			if radius > 1.0 && index.fract() != 0.0 {
+				let spectrum = &mut self.output_temp_buffer[..spectrum_size];
+				spread_bin(spectrum, value, k, index, radius, center, self.fft_size);
+			}else {
+				let ratio = index.fract();
+				let k_low = index.floor() as usize;
+				if k_low <= self.fft_size / 2 {
+					self.output_temp_buffer[k_low] += (1.0 - ratio) * value * center_turn(k, k_low, center, self.fft_size);
+				}
+				if k_low < self.fft_size / 2 {
+					self.output_temp_buffer[k_low + 1] += ratio * value * center_turn(k, k_low + 1, center, self.fft_size);
+				}
 			}
-			if k_low < self.window_size / 2 {
-				self.output_temp_buffer[k_low + 1] += ratio * Complex::from_polar(magnitude, new_phase);
+		}
+
+		// before the harmonics, which are made from what the bins came out as
+		if input_params.auto_makeup && energy_after_gain > f32::MIN_POSITIVE {
+			let makeup = (energy_before_gain / energy_after_gain).sqrt().min(from_db(MAX_GAIN_DB));
+			for value in self.output_temp_buffer[1..spectrum_size].iter_mut() {
+				*value *= makeup;
 			}
 		}
 
+		self.forget_silent_bins(spectrum_size);
+
+		if input_params.harmonics > 0 {
+			self.add_harmonics(spectrum_size, input_params.harmonics, input_params.harmonic_rolloff);
+		}
+
+		if input_params.robotize {
+			self.robotize(spectrum_size, input_params.window_offset);
+		}
+
 		self.output_temp_buffer[0].im = 0.0;
-		self.output_temp_buffer[self.window_size / 2].im = 0.0;
-		for i in 1..self.window_size / 2 {
-			self.output_temp_buffer[self.window_size - i] = self.output_temp_buffer[i].conj();	
+		self.output_temp_buffer[self.fft_size / 2].im = 0.0;
+
+		if input_params.preserve_energy {
+			self.match_energy(spectrum_size);
+		}
+
+		// like `pan`, only for a left and right pair
+		if input_params.decorrelation > 0.0 && input_params.channel_count == 2 && !input_params.mid_side {
+			self.decorrelate(spectrum_size, input_params);
+		}
+
+		if let Some(spectrum) = &self.spectrum {
+			let scale = self.level_scale();
+			spectrum.set_layout(spectrum_size, self.sample_rate);
+			for (k, (input, output)) in self.temp_buffer.iter().zip(self.output_temp_buffer.iter()).take(spectrum_size).enumerate() {
+				spectrum.store(k, input.norm() * scale, output.norm() * scale);
+			}
+
+			if let Some(capture) = &mut self.capture && spectrum.take_capture_request() {
+				let frame = &mut self.capture_frame[..1 + 2 * spectrum_size];
+				frame[0] = self.sample_rate;
+				let (input, output) = frame[1..].split_at_mut(spectrum_size);
+				for (k, (input, output)) in input.iter_mut().zip(output.iter_mut()).enumerate() {
+					*input = self.temp_buffer[k].norm() * scale;
+					*output = self.output_temp_buffer[k].norm() * scale;
+				}
+				capture.publish(frame);
+			}
 		}
 
-		self.ifft.process(&mut self.output_temp_buffer);
+		let _ = self.ifft.process_with_scratch(
+			&mut self.output_temp_buffer[..spectrum_size], 
+			&mut self.frame_buffer[..self.fft_size], 
+			&mut self.fft_scratch
+		);
 
+		// only the window's own span is overlap-added; what a change of the spectrum spreads into
+		// the padding is dropped
 		for i in 0..self.window_size {
 			self.output_buffer[i] += 
-				self.output_temp_buffer[i].re * 
-				window(self.window_size, i, input_params.window_offset, input_params.window_factor) / 
-				self.window_size as f32 *
+				self.frame_buffer[i] * 
+				window(self.window_size, i, input_params.window_offset, self.window_factor) / 
+				(self.fft_size as f32 * self.ola.gain) *
 				input_params.gain;
 		}
 
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SAMPLE_RATE: f32 = 48000.0;
+	const LENGTH: usize = 48000;
+
+	fn params(window_size: usize) -> InputParams {
+		InputParams { window_size, window_factor: 0.5, gain: 1.0, sample_rate: SAMPLE_RATE, ..Default::default() }
+	}
+
+	fn tone(partials: &[(f32, f32)]) -> Vec<f32> {
+		(0..LENGTH).map(|i| {
+			let t = i as f32 / SAMPLE_RATE;
+			partials.iter().map(|&(frequency, amplitude)| amplitude * (2.0 * PI * frequency * t).sin()).sum()
+		}).collect()
+	}
+
+	fn render(input: &[f32], script: &str, params: &InputParams, block_size: usize) -> Vec<f32> {
+		let mut vocoder = PhaseVocoder::new(params.window_size, params.sample_rate);
+		vocoder.update_mapping(script).unwrap();
+		let mut output = input.to_vec();
+		for block in output.chunks_mut(block_size) {
+			vocoder.process(block, params);
+		}
+		output
+	}
+
+	// the amplitude of `frequency` over the second half, once every window has filled; the half is
+	// long enough that 10 Hz off is a whole number of cycles and reads as next to nothing
+	fn level_at(signal: &[f32], frequency: f32) -> f32 {
+		let steady = &signal[signal.len() / 2..];
+		let omega = 2.0 * PI * frequency / SAMPLE_RATE;
+		let (re, im) = steady.iter().enumerate().fold((0.0_f32, 0.0_f32), |(re, im), (i, value)| {
+			let n = (signal.len() / 2 + i) as f32;
+			(re + value * (omega * n).cos(), im - value * (omega * n).sin())
+		});
+		2.0 * (re * re + im * im).sqrt() / steady.len() as f32
+	}
+
+	#[test]
+	fn pitch_shift_moves_a_sine_to_the_shifted_pitch() {
+		let input = tone(&[(440.0, 0.5)]);
+		// locked, the shifted sine keeps the input's level; unlocked, it loses the 5 dB or so the
+		// README owns up to, and no more
+		for (phase_lock, expected) in [(false, -5.3), (true, 0.0)] {
+			let output = render(&input, "", &InputParams { pitch_shift: 12.0, phase_lock, ..params(2048) }, 256);
+			let octave = level_at(&output, 880.0);
+			let db = 20.0 * (octave / 0.5).log10();
+			assert!((db - expected).abs() < 1.0, "{phase_lock}: {db} dB");
+			for off in [440.0, 870.0, 890.0] {
+				assert!(level_at(&output, off) < octave / 10.0, "{phase_lock}: {off} Hz");
+			}
+		}
+	}
+
+	#[test]
+	fn window_size_compares_against_integer_literals() {
+		let input = tone(&[(440.0, 0.5)]);
+		let script = "if window_size == 1024 && window_size / 2 + bin > 0 { magnitude = 0.0; }";
+		assert!(rms(&render(&input, script, &params(1024), 256)) < 1e-3);
+		assert!(rms(&render(&input, script, &params(2048), 256)) > 0.1);
+	}
+
+	#[test]
+	fn phase_lock_keeps_a_harmonic_tone_at_the_shifted_pitch() {
+		let ratio = 2.0_f32.powf(7.0 / 12.0);
+		let partials: Vec<(f32, f32)> = (1..=5).map(|h| (220.0 * h as f32, 0.3 / h as f32)).collect();
+		let output = render(&tone(&partials), "", &InputParams { pitch_shift: 7.0, phase_lock: true, ..params(2048) }, 256);
+		for &(frequency, amplitude) in &partials {
+			let moved = level_at(&output, frequency * ratio);
+			assert!((moved / amplitude).log10().abs() < 0.1, "{frequency} Hz: {moved} for {amplitude}");
+			for off in [frequency * ratio - 10.0, frequency * ratio + 10.0] {
+				assert!(level_at(&output, off) < moved / 10.0, "{frequency} Hz: {off} Hz");
+			}
+		}
+		// nothing the shift lands near
+		assert!(level_at(&output, 220.0) < 0.03);
+	}
+
+	#[test]
+	fn peak_interpolation_leaves_an_identity_mapping_alone() {
+		let input = tone(&[(440.0, 0.3), (1234.5, 0.2)]);
+		for script in ["", "frequency = frequency;"] {
+			for phase_lock in [false, true] {
+				let params = InputParams { peak_interpolation: true, phase_lock, ..params(2048) };
+				let output = render(&input, script, &params, 256);
+				let latency = PhaseVocoder::new(2048, SAMPLE_RATE).latency();
+				let error = (LENGTH / 2..LENGTH).map(|i| (output[i] - input[i - latency]).abs()).fold(0.0, f32::max);
+				assert!(error < 1e-4, "{script:?} {phase_lock}: {error}");
+			}
+		}
+	}
+
+	// the power-weighted mean frequency of the second half
+	fn spectral_centroid(signal: &[f32]) -> f32 {
+		let steady = &signal[signal.len() / 2..];
+		let fft = RealFftPlanner::<f32>::new().plan_fft_forward(steady.len());
+		let mut windowed: Vec<f32> = steady.iter().enumerate()
+			.map(|(i, value)| value * window(steady.len(), i, 0.0, 0.5))
+			.collect();
+		let mut spectrum = fft.make_output_vec();
+		fft.process(&mut windowed, &mut spectrum).unwrap();
+		let bin_width = SAMPLE_RATE / steady.len() as f32;
+		let (weighted, total) = spectrum.iter().enumerate().fold((0.0, 0.0), |(weighted, total), (k, value)| {
+			(weighted + k as f32 * bin_width * value.norm_sqr(), total + value.norm_sqr())
+		});
+		weighted / total
+	}
+
+	#[test]
+	fn preserve_formants_keeps_the_envelope_where_it_was() {
+		// a 150 Hz buzz through a resonance at 1 kHz
+		let partials: Vec<(f32, f32)> = (1..=40).map(|h| {
+			let frequency = 150.0 * h as f32;
+			(frequency, 0.1 / (1.0 + ((frequency - 1000.0) / 300.0).powi(2)))
+		}).collect();
+		let input = tone(&partials);
+		let shifted = InputParams { pitch_shift: 12.0, phase_lock: true, ..params(2048) };
+		let moved = render(&input, "", &shifted, 256);
+		let preserved = render(&input, "", &InputParams { preserve_formants: true, ..shifted }, 256);
+		let centroid = spectral_centroid(&input);
+		assert!(spectral_centroid(&moved) > 1.6 * centroid);
+		assert!((spectral_centroid(&preserved) / centroid - 1.0).abs() < 0.25);
+		// the partials are moved, only their levels follow the envelope
+		assert!(level_at(&preserved, 300.0) > 0.5 * level_at(&moved, 300.0));
+		assert!(level_at(&preserved, 1200.0) > 0.5 * level_at(&input, 1200.0));
+	}
+
+	#[test]
+	fn preserve_formants_passes_an_identity_mapping_through_at_the_largest_window() {
+		// whole cycles, so the repeats join up
+		let input = tone(&[(440.0, 0.3), (1234.0, 0.2)]).repeat(3);
+		let latency = PhaseVocoder::new(MAX_WINDOW_SIZE, SAMPLE_RATE).latency();
+		let output = render(&input, "", &InputParams { preserve_formants: true, ..params(MAX_WINDOW_SIZE) }, 256);
+		let error = (latency + MAX_WINDOW_SIZE..input.len()).map(|i| (output[i] - input[i - latency]).abs()).fold(0.0, f32::max);
+		assert!(error < 1e-4, "{error}");
+	}
+
+	// the frequency of the strongest bin over the second half
+	fn dominant_frequency(signal: &[f32]) -> f32 {
+		let steady = &signal[signal.len() / 2..];
+		let fft = RealFftPlanner::<f32>::new().plan_fft_forward(steady.len());
+		let mut windowed: Vec<f32> = steady.iter().enumerate()
+			.map(|(i, value)| value * window(steady.len(), i, 0.0, 0.5))
+			.collect();
+		let mut spectrum = fft.make_output_vec();
+		fft.process(&mut windowed, &mut spectrum).unwrap();
+		let peak = (0..spectrum.len()).max_by(|&i, &j| spectrum[i].norm().total_cmp(&spectrum[j].norm())).unwrap();
+		peak as f32 * SAMPLE_RATE / steady.len() as f32
+	}
+
+	#[test]
+	fn stretching_twice_as_long_keeps_the_pitch() {
+		let input = tone(&[(440.0, 0.5)]);
+		for phase_lock in [false, true] {
+			let output = render(&input, "", &InputParams { stretch: 2.0, phase_lock, ..params(2048) }, 256);
+			let dominant = dominant_frequency(&output);
+			assert!((dominant - 440.0).abs() < 2.0, "{phase_lock}: {dominant} Hz");
+			assert!(level_at(&output, 220.0) < 0.01 && level_at(&output, 880.0) < 0.01, "{phase_lock}");
+		}
+	}
+
+	#[test]
+	fn reset_starts_over_the_same_way() {
+		let input = tone(&[(440.0, 0.3), (1234.5, 0.2)]);
+		let params = InputParams { pitch_shift: 5.0, whisper: 0.3, smoothing: 0.5, ..params(1024) };
+		let mut vocoder = PhaseVocoder::new(1024, SAMPLE_RATE);
+		vocoder.update_mapping("frequency *= 1.5;").unwrap();
+		let mut first = input.clone();
+		vocoder.process(&mut first, &params);
+		vocoder.reset();
+		let mut second = input.clone();
+		vocoder.process(&mut second, &params);
+		assert!(first == second);
+	}
+
+	#[test]
+	fn flush_lets_out_the_last_latency_of_input() {
+		let input = tone(&[(440.0, 0.3), (1234.5, 0.2)]);
+		let params = params(2048);
+		let mut vocoder = PhaseVocoder::new(2048, SAMPLE_RATE);
+		let latency = vocoder.latency();
+		let mut output = input.clone();
+		vocoder.process(&mut output, &params);
+		// past what the flush is owed, to see it's left alone
+		let mut tail = vec![7.0; latency + 100];
+		assert_eq!(vocoder.flush(&mut tail, &params), latency);
+		assert!(tail[latency..].iter().all(|&value| value == 7.0));
+
+		output.extend_from_slice(&tail[..latency]);
+		assert_eq!(output.len(), input.len() + latency);
+		let error = (latency + 2048..output.len()).map(|i| (output[i] - input[i - latency]).abs()).fold(0.0, f32::max);
+		assert!(error < 1e-4, "{error}");
+		let energy = |signal: &[f32]| signal.iter().map(|value| value * value).sum::<f32>();
+		let ratio = energy(&tail[..latency]) / energy(&input[input.len() - latency..]);
+		assert!((ratio - 1.0).abs() < 1e-3, "{ratio}");
+	}
+
+	#[test]
+	fn a_failing_script_passes_the_bins_it_failed_on_through() {
+		// only the loud tone's bins make it throw, the quiet one's are moved up an octave
+		let input = tone(&[(440.0, 0.5), (3000.0, 0.01)]);
+		let script = "if magnitude > 30.0 { throw \"too loud\"; } frequency *= 2.0;";
+		let params = InputParams { magnitude_in_db: true, ..params(2048) };
+		let mut vocoder = PhaseVocoder::new(2048, SAMPLE_RATE);
+		vocoder.update_mapping(script).unwrap();
+		let mut output = input.clone();
+		vocoder.process(&mut output, &params);
+
+		assert!(vocoder.script_error().is_some_and(|error| error.contains("too loud")), "{:?}", vocoder.script_error());
+		let kept = level_at(&output, 440.0);
+		assert!((20.0 * (kept / 0.5).log10()).abs() < 0.5, "{kept}");
+		assert!(level_at(&output, 3000.0) < 0.001);
+		assert!(level_at(&output, 6000.0) > 0.0025);
+		// and it stays latched while the script keeps failing
+		vocoder.process(&mut output.clone(), &params);
+		assert!(vocoder.script_error().is_some());
+	}
+
+
+	fn rms(signal: &[f32]) -> f32 {
+		let steady = &signal[signal.len() / 2..];
+		(steady.iter().map(|value| value * value).sum::<f32>() / steady.len() as f32).sqrt()
+	}
+
+	#[test]
+	fn window_factor_leaves_the_level_alone() {
+		let input = tone(&[(440.0, 0.5)]);
+		let level = |pitch_shift: f32, window_factor: f32, phase_lock: bool| {
+			let params = InputParams { pitch_shift, window_factor, phase_lock, ..params(2048) };
+			20.0 * rms(&render(&input, "", &params, 256)).log10()
+		};
+		let reference = 20.0 * rms(&input).log10();
+		// without phase lock only what isn't moved keeps its level, see the README
+		for (pitch_shift, phase_lock) in [(0.0, false), (0.0, true), (0.5, true), (7.0, true), (12.0, true), (-12.0, true)] {
+			let levels: Vec<f32> = (4..=10).map(|tenths| level(pitch_shift, tenths as f32 / 10.0, phase_lock)).collect();
+			for level in &levels {
+				assert!((level - reference).abs() < 1.0, "{pitch_shift} st {phase_lock}: {levels:?}");
+			}
+		}
+	}
+
+	#[test]
+	fn an_identity_mapping_passes_the_input_through() {
+		let input = tone(&[(440.0, 0.3), (1234.5, 0.2), (5000.0, 0.1)]);
+		for window_size in [512, 1024, 2048] {
+			let latency = PhaseVocoder::new(window_size, SAMPLE_RATE).latency();
+			for window_factor in [0.2, 0.5, 0.8, 1.0] {
+				let output = render(&input, "", &InputParams { window_factor, ..params(window_size) }, 256);
+				// once the first window has filled
+				let error = (latency + window_size..LENGTH).map(|i| (output[i] - input[i - latency]).abs()).fold(0.0, f32::max);
+				assert!(error < 1e-4, "{window_size} {window_factor}: {error}");
+			}
+		}
+	}
+
+	#[test]
+	fn changing_the_window_size_does_not_allocate() {
+		let input = tone(&[(440.0, 0.3), (1234.5, 0.2)]);
+		// no script, since Rhai allocates on its own; the shift still moves every bin
+		let mut vocoder = PhaseVocoder::new(2048, SAMPLE_RATE);
+		for window_size in [512, MAX_WINDOW_SIZE, 128, 4096, 2048, 512] {
+			let params = InputParams { pitch_shift: 7.0, phase_lock: true, ..params(window_size) };
+			let mut output = input.clone();
+			assert_no_alloc::assert_no_alloc(|| {
+				for block in output.chunks_mut(256) {
+					vocoder.process(block, &params);
+				}
+			});
+			assert_eq!(vocoder.window_size, window_size);
+		}
+	}
+
+	#[test]
+	fn latency_is_where_an_impulse_comes_out() {
+		const AT: usize = 5000;
+		for window_size in [512, 2048, 8192] {
+			for low_latency in [false, true] {
+				let mut vocoder = PhaseVocoder::new(window_size, SAMPLE_RATE);
+				vocoder.renew_low_latency(low_latency);
+				let mut signal = vec![0.0; LENGTH];
+				signal[AT] = 1.0;
+				vocoder.process(&mut signal, &InputParams { low_latency, ..params(window_size) });
+				let peak = (0..LENGTH).max_by(|&i, &j| signal[i].abs().total_cmp(&signal[j].abs())).unwrap();
+				assert_eq!(peak - AT, vocoder.latency(), "{window_size} {low_latency}");
+				assert!((signal[peak] - 1.0).abs() < 1e-3, "{window_size} {low_latency}: {}", signal[peak]);
+			}
+		}
+	}
+
+	#[test]
+	fn block_size_does_not_change_the_output() {
+		let input = tone(&[(440.0, 0.3), (1234.5, 0.2)]);
+		// low latency is switched to after a fade, which has to land on the same sample too
+		for low_latency in [false, true] {
+			let params = InputParams { low_latency, ..params(1024) };
+			let reference = render(&input, "frequency *= 1.5;", &params, 64);
+			for block_size in [1, 7, 333] {
+				assert!(render(&input, "frequency *= 1.5;", &params, block_size) == reference, "{low_latency} {block_size}");
+			}
+		}
+	}
+
+	#[test]
+	fn output_is_read_where_the_old_wrapped_count_read_it() {
+		// the output used to be read at a count wrapped around the buffer on every sample and moved
+		// back a hop on every hop; read that way from the same buffer, every sample comes out the same
+		let input = tone(&[(440.0, 0.3), (1234.5, 0.2)]);
+		for low_latency in [false, true] {
+			let params = InputParams { pitch_shift: 7.0, low_latency, ..params(1024) };
+			let mut vocoder = PhaseVocoder::new(1024, SAMPLE_RATE);
+			// past the switch to low latency
+			vocoder.process(&mut vec![0.0; 4096], &params);
+			vocoder.reset();
+
+			let capacity = vocoder.output_buffer.capacity();
+			let mut count = vocoder.output_start();
+			for &sample in &input[..LENGTH / 4] {
+				let expected = if low_latency {
+					vocoder.output_buffer[count] * vocoder.early_gain[count - vocoder.output_start()]
+				}else {
+					vocoder.output_buffer[count]
+				};
+				let mut output = [sample];
+				vocoder.process(&mut output, &params);
+				assert_eq!(output[0], expected);
+
+				count = (count + 1) % capacity;
+				if vocoder.input_count == 0 {
+					count = (count + capacity - vocoder.frame_hop) % capacity;
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use nih_plug::prelude::*;
+use serde::Deserialize;
+use serde::Serialize;
+use time::OffsetDateTime;
+use crate::Arguments;
+use crate::ScriptSlot;
+use crate::phase_vocoder::BANDS;
+
+const PRESET_VERSION: u32 = 1;
+const PRESET_EXTENSION: &str = "json";
+
+lazy_static::lazy_static! {
+	pub static ref PRESET_DIR: PathBuf = {
+		let mut path = dirs::document_dir()
+			.or_else(|| std::fs::canonicalize(".").ok())
+			.unwrap_or_else(|| PathBuf::from("."));
+		path.push("i_am_freq_remapper_presets");
+		path
+	};
+}
+
+/// A complete patch: every param as a normalized value keyed by its id, plus the scripts.
+/// Keys this version doesn't know are ignored on load, and params missing from the file keep
+/// their current value, so presets survive params being added or removed.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Preset {
+	#[serde(default)]
+	pub version: u32,
+	#[serde(default)]
+	pub params: BTreeMap<String, f32>,
+	#[serde(default)]
+	pub map_code: String,
+	/// Empty when the right channel runs `map_code` too.
+	#[serde(default)]
+	pub right_map_code: String,
+	/// From the low band up, empty where a band runs the channel's script.
+	#[serde(default)]
+	pub band_codes: [String; BANDS],
+}
+
+impl Preset {
+	pub fn capture(params: &Arguments) -> Self {
+		let values = params.param_map().into_iter().map(|(id, ptr, _)| {
+			// SAFETY: the pointers come from `params`, which outlives this call
+			(id, unsafe { ptr.unmodulated_normalized_value() })
+		}).collect();
+
+		Self {
+			version: PRESET_VERSION,
+			params: values,
+			map_code: params.map_code.read().unwrap().clone().unwrap_or_default(),
+			right_map_code: params.right_map_code.read().unwrap().clone().unwrap_or_default(),
+			band_codes: std::array::from_fn(|band| params.script(ScriptSlot::Band(band)).read().unwrap().clone().unwrap_or_default()),
+		}
+	}
+
+	/// Sets every param through `setter` so the host sees the changes, then swaps in the scripts.
+	pub fn apply(&self, params: &Arguments, setter: &ParamSetter<'_>) {
+		for (id, ptr, _) in params.param_map() {
+			if let Some(value) = self.params.get(&id) {
+				// SAFETY: same as in `capture`
+				unsafe {
+					setter.raw_context.raw_begin_set_parameter(ptr);
+					setter.raw_context.raw_set_parameter_normalized(ptr, *value);
+					setter.raw_context.raw_end_set_parameter(ptr);
+				}
+			}
+		}
+
+		*params.map_code.write().unwrap() = Ok(self.map_code.clone());
+		*params.right_map_code.write().unwrap() = Ok(self.right_map_code.clone());
+		for (band, code) in self.band_codes.iter().enumerate() {
+			*params.script(ScriptSlot::Band(band)).write().unwrap() = Ok(code.clone());
+		}
+		*params.date.write().unwrap() = OffsetDateTime::now_utc().to_string();
+	}
+}
+
+/// Two in-memory patches to flip between. The live params always belong to `active`;
+/// the other slot is a snapshot until it's switched to.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct AbSlots {
+	pub active: usize,
+	pub slots: [Option<Preset>; 2],
+}
+
+impl AbSlots {
+	/// Stores the live params in the active slot and applies `slot`. An empty slot starts as a
+	/// copy of the current patch. The params go through the host like any automation and the
+	/// scripts are swapped at the start of the next block, so switching doesn't interrupt audio.
+	pub fn switch(&mut self, slot: usize, params: &Arguments, setter: &ParamSetter<'_>) {
+		if slot == self.active || slot >= self.slots.len() {
+			return;
+		}
+
+		let current = Preset::capture(params);
+		match &self.slots[slot] {
+			Some(preset) => preset.apply(params, setter),
+			None => self.slots[slot] = Some(current.clone()),
+		}
+		self.slots[self.active] = Some(current);
+		self.active = slot;
+	}
+
+	/// Overwrites B with A, taking A from the live params if it's the active slot.
+	pub fn copy_a_to_b(&mut self, params: &Arguments, setter: &ParamSetter<'_>) {
+		if self.active == 0 {
+			self.slots[0] = Some(Preset::capture(params));
+		}
+		self.slots[1] = self.slots[0].clone();
+		if self.active == 1 && let Some(preset) = &self.slots[1] {
+			preset.apply(params, setter);
+		}
+	}
+}
+
+fn preset_path(name: &str) -> Result<PathBuf, String> {
+	if name.is_empty() || name.contains(['/', '\\']) {
+		return Err(format!("invalid preset name `{name}`"));
+	}
+
+	let mut path = PRESET_DIR.clone();
+	path.push(name);
+	path.set_extension(PRESET_EXTENSION);
+	Ok(path)
+}
+
+pub fn save(name: &str, preset: &Preset) -> Result<(), String> {
+	let path = preset_path(name)?;
+	std::fs::create_dir_all(&*PRESET_DIR).map_err(|e| format!("{e}"))?;
+	let data = serde_json::to_string_pretty(preset).map_err(|e| format!("{e}"))?;
+	std::fs::write(path, data).map_err(|e| format!("{e}"))
+}
+
+pub fn load(name: &str) -> Result<Preset, String> {
+	let data = std::fs::read_to_string(preset_path(name)?).map_err(|e| format!("{e}"))?;
+	serde_json::from_str(&data).map_err(|e| format!("{e}"))
+}
+
+/// Names of every preset in [`PRESET_DIR`], sorted.
+pub fn list() -> Vec<String> {
+	let Ok(entries) = std::fs::read_dir(&*PRESET_DIR) else {
+		return Vec::new();
+	};
+
+	let mut names = entries
+		.filter_map(|entry| entry.ok())
+		.map(|entry| entry.path())
+		.filter(|path| path.extension().is_some_and(|extension| extension == PRESET_EXTENSION))
+		.filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+		.collect::<Vec<_>>();
+	names.sort();
+	names
+}
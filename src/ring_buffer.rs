@@ -1,21 +1,141 @@
+use std::fmt;
 use std::ops::IndexMut;
 use std::ops::Index;
+use std::iter::Chain;
+use std::slice;
 
+pub type Iter<'a, T> = Chain<slice::Iter<'a, T>, slice::Iter<'a, T>>;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "Snapshot<T>")]
 pub struct RingBuffer<T: Default> {
 	capacity: usize,
 	current_pos: usize,
+	len: usize,
+	// `Some(capacity - 1)` when built by `with_pow2_capacity`, so wrapping is a bitwise and
+	mask: Option<usize>,
 	buffer: Vec<T>
 }
 
+/// What a serialized [`RingBuffer`] is read back into, so a damaged snapshot is rejected
+/// instead of indexing out of bounds later.
+#[derive(serde::Deserialize)]
+struct Snapshot<T> {
+	capacity: usize,
+	current_pos: usize,
+	len: usize,
+	mask: Option<usize>,
+	buffer: Vec<T>,
+}
+
+impl<T: Default> TryFrom<Snapshot<T>> for RingBuffer<T> {
+	type Error = String;
+
+	fn try_from(snapshot: Snapshot<T>) -> Result<Self, String> {
+		let Snapshot { capacity, current_pos, len, mask, buffer } = snapshot;
+		let valid = capacity <= buffer.len()
+			&& (current_pos < capacity || current_pos == 0)
+			&& len <= capacity
+			&& mask.is_none_or(|mask| capacity.is_power_of_two() && mask == capacity - 1);
+		if !valid {
+			return Err(format!("inconsistent ring buffer: capacity {capacity}, position {current_pos}, length {len}, storage {}", buffer.len()))
+		}
+
+		Ok(Self { capacity, current_pos, len, mask, buffer })
+	}
+}
+
 impl<T: Default + Clone> RingBuffer<T> {
 	pub fn new(capacity: usize) -> Self {
+		Self::with_max_capacity(capacity, capacity)
+	}
+
+	/// Allocates storage for `max_capacity` elements up front so that
+	/// [`RingBuffer::set_capacity`] never has to reallocate.
+	pub fn with_max_capacity(capacity: usize, max_capacity: usize) -> Self {
 		Self {
 			capacity,
 			current_pos: 0,
-			buffer: vec![T::default(); capacity]
+			len: 0,
+			mask: None,
+			buffer: vec![T::default(); max_capacity.max(capacity)]
+		}
+	}
+
+	/// Same as [`RingBuffer::new`], but wraps indices with a bitmask instead of a modulo.
+	/// `capacity` must be a power of two, and so must every later [`RingBuffer::set_capacity`].
+	pub fn with_pow2_capacity(capacity: usize) -> Self {
+		debug_assert!(capacity.is_power_of_two(), "capacity {capacity} is not a power of two");
+		Self {
+			mask: Some(capacity - 1),
+			..Self::new(capacity)
 		}
 	}
 
+	/// Changes the logical capacity inside the allocated storage and resets the contents to defaults.
+	/// Returns `false` without touching anything if `capacity` exceeds the allocated storage.
+	pub fn set_capacity(&mut self, capacity: usize) -> bool {
+		if capacity > self.buffer.len() {
+			return false
+		}
+
+		if self.mask.is_some() {
+			debug_assert!(capacity.is_power_of_two(), "capacity {capacity} is not a power of two");
+			self.mask = Some(capacity - 1);
+		}
+
+		self.capacity = capacity;
+		self.clear();
+
+		true
+	}
+
+	/// Changes the capacity while keeping the newest `min(old, new)` elements, still the newest
+	/// after the change; when growing, the slots in front of them are defaults. Unlike
+	/// [`RingBuffer::set_capacity`] this may go beyond the allocated storage, which then reallocates.
+	pub fn resize(&mut self, capacity: usize) {
+		let old_capacity = self.capacity;
+		let keep = old_capacity.min(capacity);
+		if self.mask.is_some() {
+			debug_assert!(capacity.is_power_of_two(), "capacity {capacity} is not a power of two");
+			self.mask = Some(capacity - 1);
+		}
+		if capacity > self.buffer.len() {
+			self.buffer.resize(capacity, T::default());
+		}
+
+		// oldest first from slot 0, then the newest `keep` moved to the end of the new capacity
+		self.buffer[..old_capacity].rotate_left(self.current_pos);
+		self.buffer[..old_capacity].rotate_left(old_capacity - keep);
+		for value in &mut self.buffer[keep..capacity] {
+			*value = T::default();
+		}
+		self.buffer[..capacity].rotate_right(capacity - keep);
+
+		self.capacity = capacity;
+		self.current_pos = 0;
+		self.len = self.len.min(capacity);
+	}
+
+	/// Resets every slot inside the current capacity to its default, keeping the capacity.
+	pub fn clear(&mut self) {
+		self.current_pos = 0;
+		self.len = 0;
+		for value in &mut self.buffer[..self.capacity] {
+			*value = T::default();
+		}
+	}
+
+	/// Sets every slot inside the current capacity to `value`, as if it had been pushed `capacity`
+	/// times into an empty buffer: full, with `current_pos` back at 0. Never reallocates.
+	pub fn fill(&mut self, value: T) {
+		self.current_pos = 0;
+		self.len = self.capacity;
+		self.buffer[..self.capacity].fill(value);
+	}
+
+	/// Pushes `len` defaults, shifting the oldest `len` elements out.
+	/// Returns `false` without touching anything if `len` exceeds the capacity.
 	pub fn extend_defaults(&mut self, len: usize) -> bool {
 		if self.capacity() < len {
 			return false
@@ -27,6 +147,34 @@ impl<T: Default + Clone> RingBuffer<T> {
 
 		true
 	}
+
+	/// Pushes every element of `data` in order. Only the last `capacity` elements survive
+	/// if `data` is longer than the buffer.
+	pub fn push_slice(&mut self, data: &[T]) {
+		let data = &data[data.len().saturating_sub(self.capacity)..];
+		let first_len = data.len().min(self.capacity - self.current_pos);
+		let (first, second) = data.split_at(first_len);
+
+		self.buffer[self.current_pos..self.current_pos + first_len].clone_from_slice(first);
+		self.buffer[..second.len()].clone_from_slice(second);
+		self.current_pos = self.wrap(self.current_pos + data.len());
+		self.len = (self.len + data.len()).min(self.capacity);
+	}
+
+	/// Fills `out` with the contents in logical order, stopping at whichever runs out first.
+	pub fn copy_into(&self, out: &mut [T]) {
+		let (head, tail) = self.as_slices();
+		let head_len = head.len().min(out.len());
+		let tail_len = tail.len().min(out.len() - head_len);
+
+		out[..head_len].clone_from_slice(&head[..head_len]);
+		out[head_len..head_len + tail_len].clone_from_slice(&tail[..tail_len]);
+	}
+
+	/// The contents in logical order, i.e. `self[0]` first. Allocates, so keep it off the audio thread.
+	pub fn to_vec(&self) -> Vec<T> {
+		self.iter().cloned().collect()
+	}
 }
 
 impl<T: Default> RingBuffer<T> {
@@ -34,9 +182,119 @@ impl<T: Default> RingBuffer<T> {
 		self.capacity
 	}
 
+	/// Number of pushed elements still held by the buffer, at most [`RingBuffer::capacity`].
+	/// Until the buffer is full the remaining slots are stale defaults.
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Indexes like `self[idx]`, but returns `None` for slots that don't hold a pushed element yet
+	/// and for `idx >= capacity` instead of wrapping.
+	pub fn get(&self, idx: usize) -> Option<&T> {
+		if idx >= self.capacity || idx < self.capacity - self.len {
+			return None
+		}
+
+		Some(&self[idx])
+	}
+
+	/// Mutable counterpart of [`RingBuffer::get`].
+	pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+		if idx >= self.capacity || idx < self.capacity - self.len {
+			return None
+		}
+
+		Some(&mut self[idx])
+	}
+
+	#[inline]
+	fn wrap(&self, idx: usize) -> usize {
+		match self.mask {
+			Some(mask) => idx & mask,
+			None => idx % self.capacity,
+		}
+	}
+
 	pub fn push(&mut self, value: T) {
 		self.buffer[self.current_pos] = value;
-		self.current_pos = (self.current_pos + 1) % self.capacity;
+		self.current_pos = self.wrap(self.current_pos + 1);
+		self.len = (self.len + 1).min(self.capacity);
+	}
+
+	/// Returns the two physical segments of the buffer, which together hold the contents
+	/// in logical order (oldest first).
+	pub fn as_slices(&self) -> (&[T], &[T]) {
+		let (tail, head) = self.buffer[..self.capacity].split_at(self.current_pos);
+		(head, tail)
+	}
+
+	/// Iterates over the contents in logical order, i.e. the same order as indexing from `0`.
+	pub fn iter(&self) -> Iter<'_, T> {
+		let (head, tail) = self.as_slices();
+		head.iter().chain(tail.iter())
+	}
+}
+
+/// Keeps the capacity, the storage allocated behind it and where the buffer has wrapped to, so the
+/// clone indexes exactly like the original and can grow as far.
+impl<T: Default + Clone> Clone for RingBuffer<T> {
+	fn clone(&self) -> Self {
+		Self {
+			capacity: self.capacity,
+			current_pos: self.current_pos,
+			len: self.len,
+			mask: self.mask,
+			buffer: self.buffer.clone()
+		}
+	}
+
+	/// Reuses this buffer's storage if it's as large as `source`'s, so a snapshot can be restored
+	/// on the audio thread without allocating.
+	fn clone_from(&mut self, source: &Self) {
+		self.capacity = source.capacity;
+		self.current_pos = source.current_pos;
+		self.len = source.len;
+		self.mask = source.mask;
+		self.buffer.clone_from(&source.buffer);
+	}
+}
+
+/// A full buffer holding `values`, oldest first, so `self[0]` is `values[0]`. The capacity is
+/// `values.len()` and the storage is the vec itself.
+impl<T: Default> From<Vec<T>> for RingBuffer<T> {
+	fn from(values: Vec<T>) -> Self {
+		Self {
+			capacity: values.len(),
+			current_pos: 0,
+			len: values.len(),
+			mask: None,
+			buffer: values
+		}
+	}
+}
+
+impl<'a, T: Default> IntoIterator for &'a RingBuffer<T> {
+	type Item = &'a T;
+	type IntoIter = Iter<'a, T>;
+
+	fn into_iter(self) -> Iter<'a, T> {
+		self.iter()
+	}
+}
+
+/// Prints the contents in logical order (starting at `current_pos`) rather than storage order.
+impl<T: Default + fmt::Debug> fmt::Debug for RingBuffer<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("RingBuffer")
+			.field("capacity", &self.capacity)
+			.field("current_pos", &self.current_pos)
+			.field("len", &self.len)
+			.field("contents", &self.iter().collect::<Vec<_>>())
+			.finish()
 	}
 }
 
@@ -44,10 +302,12 @@ impl<T: Default> Index<usize> for RingBuffer<T> {
 	type Output = T;
 
 	fn index(&self, idx: usize) -> &T {
-		&self.buffer[(idx + self.current_pos) % self.capacity]
+		&self.buffer[self.wrap(idx + self.current_pos)]
 	}
 }
 
+/// Wraps like the `usize` index, so negative indices count back from the end: `self[-1]` is
+/// the most recently pushed element and `self[-(capacity as isize)]` is `self[0]`, the oldest.
 impl<T: Default> Index<isize> for RingBuffer<T> {
 	type Output = T;
 
@@ -62,7 +322,8 @@ impl<T: Default> Index<isize> for RingBuffer<T> {
 
 impl<T: Default> IndexMut<usize> for RingBuffer<T> {
 	fn index_mut(&mut self, idx: usize) -> &mut T {
-		&mut self.buffer[(idx + self.current_pos) % self.capacity]
+		let idx = self.wrap(idx + self.current_pos);
+		&mut self.buffer[idx]
 	}
 }
 
@@ -74,4 +335,221 @@ impl<T: Default> IndexMut<isize> for RingBuffer<T> {
 		}
 		&mut self[idx as usize]
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn pushed(mut buffer: RingBuffer<i32>, values: std::ops::Range<i32>) -> RingBuffer<i32> {
+		for value in values {
+			buffer.push(value);
+		}
+		buffer
+	}
+
+	#[test]
+	fn wraps_around_with_the_oldest_first() {
+		for buffer in [pushed(RingBuffer::new(4), 0..10), pushed(RingBuffer::with_pow2_capacity(4), 0..10)] {
+			assert_eq!(buffer.len(), 4);
+			assert_eq!((0..4).map(|i| buffer[i as usize]).collect::<Vec<_>>(), [6, 7, 8, 9]);
+			assert_eq!(buffer[4_usize], 6);
+			assert_eq!(buffer.to_vec(), [6, 7, 8, 9]);
+		}
+	}
+
+	#[test]
+	fn negative_indices_count_back_from_the_newest() {
+		let buffer = pushed(RingBuffer::new(4), 0..6);
+		assert_eq!(buffer[-1_isize], 5);
+		assert_eq!(buffer[-2_isize], 4);
+		assert_eq!(buffer[-4_isize], buffer[0_usize]);
+		assert_eq!(buffer[-5_isize], 5);
+	}
+
+	#[test]
+	fn extend_defaults_shifts_in_defaults_up_to_the_capacity() {
+		let mut buffer = pushed(RingBuffer::new(4), 1..3);
+		assert_eq!(buffer.len(), 2);
+		assert!(buffer.extend_defaults(1));
+		assert_eq!(buffer.len(), 3);
+		assert_eq!(buffer.to_vec(), [0, 1, 2, 0]);
+		assert!(buffer.extend_defaults(4));
+		assert_eq!(buffer.len(), 4);
+		assert_eq!(buffer.to_vec(), [0, 0, 0, 0]);
+
+		let mut buffer = pushed(RingBuffer::new(4), 1..5);
+		assert!(!buffer.extend_defaults(5));
+		assert_eq!(buffer.to_vec(), [1, 2, 3, 4]);
+	}
+
+	#[test]
+	fn writes_land_where_reads_find_them() {
+		let mut buffer = pushed(RingBuffer::new(5), 0..7);
+		buffer[0_usize] = 100;
+		buffer[-1_isize] = 200;
+		buffer[7_usize] = 300;
+		assert_eq!(buffer[0_usize], 100);
+		assert_eq!(buffer[4_usize], 200);
+		assert_eq!(buffer[-3_isize], 300);
+		*buffer.get_mut(1).unwrap() = 400;
+		assert_eq!(buffer.get(1), Some(&400));
+		assert_eq!(buffer.to_vec(), [100, 400, 300, 5, 200]);
+	}
+
+	#[test]
+	fn slices_and_iter_go_oldest_first() {
+		// not full yet: the slots nothing was pushed into come first, as stale defaults
+		let buffer = pushed(RingBuffer::new(5), 1..3);
+		assert_eq!(buffer.as_slices(), ([0, 0, 0].as_slice(), [1, 2].as_slice()));
+		assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), [0, 0, 0, 1, 2]);
+
+		// wrapped around the end of the storage
+		for buffer in [pushed(RingBuffer::new(5), 0..7), pushed(RingBuffer::with_max_capacity(5, 8), 0..7)] {
+			assert_eq!(buffer.as_slices(), ([2, 3, 4].as_slice(), [5, 6].as_slice()));
+			assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), [2, 3, 4, 5, 6]);
+			assert!(buffer.iter().enumerate().all(|(i, value)| *value == buffer[i]));
+		}
+
+		// full and back at the start of the storage, all in the first slice
+		let buffer = pushed(RingBuffer::new(4), 0..8);
+		assert_eq!(buffer.as_slices(), ([4, 5, 6, 7].as_slice(), [].as_slice()));
+	}
+
+	#[test]
+	fn push_slice_matches_pushing_one_at_a_time() {
+		// straddling the end of the storage, then longer than the whole buffer
+		for (before, slice) in [(0..3, 10..14), (0..3, 10..23), (0..0, 10..15)] {
+			let data: Vec<i32> = slice.clone().collect();
+			let mut buffer = pushed(RingBuffer::new(5), before.clone());
+			buffer.push_slice(&data);
+			let expected = pushed(pushed(RingBuffer::new(5), before), slice);
+			assert_eq!(buffer.to_vec(), expected.to_vec(), "{data:?}");
+			assert_eq!(buffer.len(), expected.len());
+			assert_eq!(buffer[-1_isize], expected[-1_isize]);
+		}
+	}
+
+	#[test]
+	fn copy_into_stops_at_whichever_runs_out() {
+		let buffer = pushed(RingBuffer::new(5), 0..7);
+		let mut short = [-1; 3];
+		buffer.copy_into(&mut short);
+		assert_eq!(short, [2, 3, 4]);
+		let mut long = [-1; 7];
+		buffer.copy_into(&mut long);
+		assert_eq!(long, [2, 3, 4, 5, 6, -1, -1]);
+	}
+
+	#[test]
+	fn resize_keeps_the_newest_contents() {
+		for buffer in [pushed(RingBuffer::new(4), 0..6), pushed(RingBuffer::with_pow2_capacity(4), 0..6)] {
+			let mut grown = buffer.clone();
+			grown.resize(8);
+			assert_eq!(grown.to_vec(), [0, 0, 0, 0, 2, 3, 4, 5]);
+			assert_eq!(grown.len(), 4);
+			assert_eq!(grown[-1_isize], 5);
+			grown.push(6);
+			assert_eq!(grown.to_vec(), [0, 0, 0, 2, 3, 4, 5, 6]);
+
+			let mut shrunk = buffer.clone();
+			shrunk.resize(2);
+			assert_eq!(shrunk.to_vec(), [4, 5]);
+			assert_eq!(shrunk.len(), 2);
+			shrunk.push(6);
+			assert_eq!(shrunk.to_vec(), [5, 6]);
+		}
+	}
+
+	#[test]
+	fn get_skips_slots_nothing_was_pushed_into() {
+		let mut buffer = pushed(RingBuffer::new(4), 1..3);
+		assert_eq!((0..5).map(|i| buffer.get(i).copied()).collect::<Vec<_>>(), [None, None, Some(1), Some(2), None]);
+		assert!(buffer.get_mut(1).is_none());
+		// indexing still wraps onto them
+		assert_eq!(buffer[0_usize], 0);
+		assert_eq!(buffer[6_usize], 1);
+
+		buffer.push(3);
+		buffer.push(4);
+		buffer.push(5);
+		assert_eq!((0..4).map(|i| buffer.get(i).copied()).collect::<Vec<_>>(), [Some(2), Some(3), Some(4), Some(5)]);
+		buffer.clear();
+		assert_eq!(buffer.get(3), None);
+	}
+
+	#[test]
+	fn indices_match_a_model() {
+		for capacity in 1..=16_usize {
+			let mut buffers = vec![RingBuffer::new(capacity), RingBuffer::with_max_capacity(capacity, 2 * capacity)];
+			if capacity.is_power_of_two() {
+				buffers.push(RingBuffer::with_pow2_capacity(capacity));
+			}
+			for mut buffer in buffers {
+				// defaults in front until it's full, the newest last
+				let mut model = vec![0; capacity];
+				for value in 1..=3 * capacity as i32 {
+					buffer.push(value);
+					model.remove(0);
+					model.push(value);
+					for i in 0..capacity {
+						assert_eq!(buffer[i], model[i], "{capacity}: [{i}] after {value}");
+						assert_eq!(buffer[i as isize - capacity as isize], model[i], "{capacity}: [{}] after {value}", i as isize - capacity as isize);
+					}
+					assert_eq!(buffer.to_vec(), model);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn a_clone_indexes_like_the_original() {
+		let buffer = pushed(RingBuffer::with_max_capacity(5, 8), 0..7);
+		let mut clone = buffer.clone();
+		let mut restored = RingBuffer::new(8);
+		restored.clone_from(&buffer);
+		for copy in [&clone, &restored] {
+			assert_eq!(copy.capacity(), 5);
+			assert_eq!(copy.len(), buffer.len());
+			assert!((0..5_usize).all(|i| copy[i] == buffer[i]));
+			assert!((-5..0_isize).all(|i| copy[i] == buffer[i]));
+		}
+		// and keeps going the same way
+		clone.push(7);
+		assert_eq!(clone.to_vec(), [3, 4, 5, 6, 7]);
+		// with all the storage it can grow into
+		assert!(clone.set_capacity(8));
+	}
+
+	#[test]
+	fn from_a_vec_is_full_and_oldest_first() {
+		let mut buffer = RingBuffer::from(vec![1, 2, 3]);
+		assert_eq!(buffer.capacity(), 3);
+		assert_eq!(buffer.len(), 3);
+		assert_eq!(buffer[0_usize], 1);
+		assert_eq!(buffer[-1_isize], 3);
+		assert_eq!(buffer.get(0), Some(&1));
+		buffer.push(4);
+		assert_eq!(buffer.to_vec(), [2, 3, 4]);
+	}
+
+	#[test]
+	fn a_snapshot_reads_back_the_same() {
+		let buffer = pushed(RingBuffer::with_pow2_capacity(4), 0..6);
+		let json = serde_json::to_string(&buffer).unwrap();
+		let restored: RingBuffer<i32> = serde_json::from_str(&json).unwrap();
+		assert_eq!(restored.to_vec(), [2, 3, 4, 5]);
+		assert_eq!(restored.len(), 4);
+		assert_eq!(restored[-1_isize], 5);
+
+		// a length past the capacity, or storage smaller than it, would index out of bounds later
+		for damaged in [
+			r#"{"capacity":4,"current_pos":2,"len":5,"mask":3,"buffer":[4,5,2,3]}"#,
+			r#"{"capacity":4,"current_pos":2,"len":4,"mask":3,"buffer":[4,5,2]}"#,
+			r#"{"capacity":4,"current_pos":4,"len":4,"mask":3,"buffer":[4,5,2,3]}"#,
+		] {
+			let error = serde_json::from_str::<RingBuffer<i32>>(damaged).err();
+			assert!(error.is_some_and(|error| error.to_string().contains("inconsistent ring buffer")), "{damaged}");
+		}
+	}
+}
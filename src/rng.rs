@@ -0,0 +1,79 @@
+use std::cell::Cell;
+use rhai::Engine;
+use rhai::FLOAT;
+use rhai::INT;
+
+/// SplitMix64. Tiny, fast enough to call for every bin, and the same seed always
+/// gives the same sequence on every platform.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct ScriptRng {
+	state: u64,
+}
+
+impl ScriptRng {
+	pub fn new(seed: u64) -> Self {
+		Self { state: seed }
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+		let mut z = self.state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+		z ^ (z >> 31)
+	}
+
+	/// Uniform in `[0, 1)`.
+	pub fn next_f32(&mut self) -> f32 {
+		(self.next_u64() >> 40) as f32 / (1_u64 << 24) as f32
+	}
+}
+
+thread_local! {
+	// the script functions are registered on a shared engine, so the processor running a
+	// script lends its generator to the current thread for the duration of the call
+	static CURRENT: Cell<ScriptRng> = const { Cell::new(ScriptRng { state: 0 }) };
+}
+
+/// Runs `f` with `rng` backing the seeded script functions, then stores the advanced state back.
+pub fn with_rng<R>(rng: &Cell<ScriptRng>, f: impl FnOnce() -> R) -> R {
+	CURRENT.set(rng.get());
+	let result = f();
+	rng.set(CURRENT.get());
+	result
+}
+
+fn next_u64() -> u64 {
+	CURRENT.with(|current| {
+		let mut rng = current.get();
+		let value = rng.next_u64();
+		current.set(rng);
+		value
+	})
+}
+
+fn next_float() -> FLOAT {
+	CURRENT.with(|current| {
+		let mut rng = current.get();
+		let value = rng.next_f32();
+		current.set(rng);
+		value as FLOAT
+	})
+}
+
+/// Overrides the `rhai_rand` functions that scripts use most with seeded versions.
+/// Functions on the engine itself take precedence over packages, so scripts don't change.
+pub fn register(engine: &mut Engine) {
+	engine.register_fn("rand", || next_u64() as INT);
+	engine.register_fn("rand", |start: INT, end: INT| {
+		if end <= start {
+			return start
+		}
+		let span = (end as i128 - start as i128 + 1) as u128;
+		(start as i128 + (next_u64() as u128 % span) as i128) as INT
+	});
+	engine.register_fn("rand_float", next_float);
+	engine.register_fn("rand_float", |start: FLOAT, end: FLOAT| start + (end - start) * next_float());
+	engine.register_fn("rand_bool", || next_u64() & 1 == 1);
+	engine.register_fn("rand_bool", |probability: FLOAT| next_float() < probability);
+}
@@ -0,0 +1,31 @@
+/// Bit `n` of a scale is the note `n` semitones above its root.
+pub const CHROMATIC: u16 = 0xfff;
+pub const MAJOR: u16 = 0b1010_1011_0101;
+pub const MINOR: u16 = 0b0101_1010_1101;
+pub const MAJOR_PENTATONIC: u16 = 0b0010_1001_0101;
+pub const MINOR_PENTATONIC: u16 = 0b0100_1010_1001;
+
+pub const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+const NAMED: [(u16, &str); 5] = [
+	(MAJOR, "Major"),
+	(MINOR, "Minor"),
+	(MAJOR_PENTATONIC, "Major Pentatonic"),
+	(MINOR_PENTATONIC, "Minor Pentatonic"),
+	(CHROMATIC, "Chromatic"),
+];
+
+/// The common scale `scale` is, if it is one.
+pub fn scale_name(scale: u16) -> Option<&'static str> {
+	NAMED.iter().find(|(named, _)| *named == scale).map(|(_, name)| *name)
+}
+
+/// Whether pitch class `note` (0 is C) is in `scale` played from `root`.
+pub fn contains(scale: u16, root: usize, note: usize) -> bool {
+	(scale >> ((note + 12 - root % 12) % 12)) & 1 == 1
+}
+
+/// `scale` with pitch class `note` switched on or off.
+pub fn toggle(scale: u16, root: usize, note: usize) -> u16 {
+	scale ^ (1 << ((note + 12 - root % 12) % 12))
+}
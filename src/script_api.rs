@@ -0,0 +1,67 @@
+/// One line of the script reference shown in the editor: a variable in a script's scope, or a
+/// function the plugin registers, with its type and what it means in both languages.
+pub struct ApiEntry {
+	pub name: &'static str,
+	pub kind: &'static str,
+	pub en_us: &'static str,
+	pub zh_cn: &'static str,
+}
+
+const fn entry(name: &'static str, kind: &'static str, en_us: &'static str, zh_cn: &'static str) -> ApiEntry {
+	ApiEntry { name, kind, en_us, zh_cn }
+}
+
+/// Every variable a script sees, in the order the vocoder sets them: the ones that stay the same
+/// for the whole frame, then the ones for the bin. This is what the reference panel lists, so a
+/// variable added to the scope has to be added here as well.
+pub const VARIABLES: [ApiEntry; 34] = [
+	entry("a", "f32", "Param a", "参数 a"),
+	entry("b", "f32", "Param b", "参数 b"),
+	entry("c", "f32", "Param c", "参数 c"),
+	entry("d", "f32", "Param d", "参数 d"),
+	entry("sound_channel_id", "i64", "Which channel this is, from 0", "当前声道的序号，从 0 开始"),
+	entry("num_channels", "i64", "How many channels the layout processes", "当前布局一共处理几个声道"),
+	entry("mid_side", "bool", "The pair is mid and side rather than left and right", "两个声道是否为中/侧信号"),
+	entry("bpm", "f32", "The host's tempo", "宿主当前的 bpm"),
+	entry("playing", "bool", "Whether the host is playing", "宿主是否正在播放"),
+	entry("looping", "bool", "Whether the host is looping", "宿主是否开启了循环播放"),
+	entry("beat", "f32", "Playhead position in quarter notes, 0 if unknown", "播放头位置，单位为拍，未知时为 0"),
+	entry("bar_phase", "f32", "Playhead position within the bar, 0 to 1", "播放头在当前小节内的位置，0 到 1"),
+	entry("daw_time", "f32", "Seconds played, 0 while stopped", "宿主播放了多少秒，不播放时为 0"),
+	entry("sys_time", "f32", "Seconds since the plugin was loaded", "插件加载以来经过的秒数"),
+	entry("window_size", "i64", "FFT window length in samples", "FFT 窗长，单位为采样"),
+	entry("window_factor", "f32", "The window factor this frame uses", "当前帧使用的窗口参数"),
+	entry("sample_rate", "f32", "Sample rate in Hz", "采样率，单位为 Hz"),
+	entry("seed", "i64", "The seed param", "随机种子参数的值"),
+	entry("sidechain", "f32", "Sidechain RMS over this hop, 0 when unconnected", "侧链在当前 hop 内的 RMS，未连接时为 0"),
+	entry("note_freq", "f32", "Frequency of the followed MIDI note in Hz", "跟随的 MIDI 音符的频率，单位为 Hz"),
+	entry("note_vel", "f32", "Velocity of the followed MIDI note, 0 with none held", "跟随的 MIDI 音符的力度，没有按下时为 0"),
+	entry("scale_root", "i64", "Root picked on the keyboard, 0 is C", "琴键上选择的主音，0 为 C"),
+	entry("scale", "i64", "Bit n is set if n semitones above the root are in the scale", "第 n 位表示主音以上 n 个半音是否在音阶中"),
+	entry("frequency", "f32", "The bin's frequency in Hz; write to move it", "频点的频率，单位为 Hz，修改即可移动频点"),
+	entry("magnitude", "f32", "The bin's magnitude; write to change it", "频点的振幅，修改即可改变"),
+	entry("bin", "i64", "The bin's index, 0 is DC", "频点序号，0 为直流"),
+	entry("phase", "f32", "Analysis phase this frame, -π to π", "本帧的分析相位，-π 到 π"),
+	entry("prev_phase", "f32", "Analysis phase the previous hop", "上一个 hop 的分析相位"),
+	entry("prev_magnitude", "f32", "Magnitude the previous hop", "上一个 hop 的振幅"),
+	entry("sustain", "f32", "Magnitude through the sustain follower, low for clicks", "经过包络跟随的振幅，短促的声音较低"),
+	entry("bin_gain", "f32", "Linear gain after mapping, 1 by default; write to change it", "映射后的线性增益，默认为 1，修改即可生效"),
+	entry("gain_db", "f32", "Gain in dB after mapping, 0 by default; write to change it", "映射后的增益，单位为 dB，默认为 0，修改即可生效"),
+	entry("pan", "f32", "Stereo position, -1 left to 1 right; write to change it", "立体声位置，-1 最左到 1 最右，修改即可生效"),
+	entry("range_mode", "i64", "Out of range bins: 0 drop, 1 clamp, 2 wrap; write to change it", "越界处理：0 丢弃，1 钳制，2 回绕，修改即可生效"),
+];
+
+/// The functions the plugin registers on top of Rhai's own and `rhai-rand`'s.
+pub const FUNCTIONS: [ApiEntry; 11] = [
+	entry("to_db(value)", "f32", "Linear amplitude to dB, -120 for silence", "线性振幅转为 dB，静音为 -120"),
+	entry("from_db(db)", "f32", "dB to linear amplitude, 0 at -120 and below", "dB 转为线性振幅，-120 及以下为 0"),
+	entry("bin_freq(bin)", "f32", "Center frequency of any bin in Hz", "任意频点的中心频率，单位为 Hz"),
+	entry("freq_to_bin(hz)", "f32", "The bin a frequency falls on, with a fraction", "频率所在的频点序号，带小数部分"),
+	entry("stretch_tune(hz, fundamental, b)", "f32", "Moves a partial of fundamental as a stiff string with inharmonicity b would", "按非谐系数 b 把基频的泛音拉伸到硬弦（如钢琴）上的位置"),
+	entry("rand()", "i64", "A random integer, from the seed", "随机整数，由随机种子决定"),
+	entry("rand(start, end)", "i64", "A random integer from start to end", "start 到 end 之间的随机整数"),
+	entry("rand_float()", "f32", "A random number from 0 to 1", "0 到 1 之间的随机数"),
+	entry("rand_float(start, end)", "f32", "A random number from start to end", "start 到 end 之间的随机数"),
+	entry("rand_bool()", "bool", "A coin flip", "随机的真或假"),
+	entry("rand_bool(probability)", "bool", "True with the given probability", "按给定概率为真"),
+];
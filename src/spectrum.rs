@@ -0,0 +1,104 @@
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use crate::phase_vocoder::MAX_WINDOW_SIZE;
+use crate::triple_buffer::Publisher;
+use crate::triple_buffer::Reader;
+use crate::triple_buffer::triple_buffer;
+
+/// The most recent input and output magnitude spectra of one channel, shared between the
+/// audio thread and the editor. Every bin is its own atomic so the audio thread never waits;
+/// a reader may see bins from two neighbouring frames, which doesn't matter for drawing.
+pub struct SpectrumData {
+	bins: AtomicUsize,
+	sample_rate: AtomicU32,
+	input: Vec<AtomicU32>,
+	output: Vec<AtomicU32>,
+	capture_requested: AtomicBool,
+	// the editor's end of the channel a single whole frame comes through, see `capture_publisher`
+	capture: Mutex<Option<Reader>>,
+}
+
+/// One frame's input and output magnitude spectra, taken whole rather than bin by bin.
+pub struct SpectrumCapture {
+	pub sample_rate: f32,
+	pub input: Vec<f32>,
+	pub output: Vec<f32>,
+}
+
+impl Default for SpectrumData {
+	fn default() -> Self {
+		let size = MAX_WINDOW_SIZE / 2 + 1;
+		Self {
+			bins: AtomicUsize::new(0),
+			sample_rate: AtomicU32::new(0),
+			input: (0..size).map(|_| AtomicU32::new(0)).collect(),
+			output: (0..size).map(|_| AtomicU32::new(0)).collect(),
+			capture_requested: AtomicBool::new(false),
+			capture: Mutex::new(None),
+		}
+	}
+}
+
+impl SpectrumData {
+	pub fn set_layout(&self, bins: usize, sample_rate: f32) {
+		self.bins.store(bins.min(self.input.len()), Ordering::Relaxed);
+		self.sample_rate.store(sample_rate.to_bits(), Ordering::Relaxed);
+	}
+
+	pub fn store(&self, k: usize, input: f32, output: f32) {
+		if k < self.input.len() {
+			self.input[k].store(input.to_bits(), Ordering::Relaxed);
+			self.output[k].store(output.to_bits(), Ordering::Relaxed);
+		}
+	}
+
+	pub fn bins(&self) -> usize {
+		self.bins.load(Ordering::Relaxed)
+	}
+
+	pub fn sample_rate(&self) -> f32 {
+		f32::from_bits(self.sample_rate.load(Ordering::Relaxed))
+	}
+
+	pub fn input(&self, k: usize) -> f32 {
+		f32::from_bits(self.input[k].load(Ordering::Relaxed))
+	}
+
+	pub fn output(&self, k: usize) -> f32 {
+		f32::from_bits(self.output[k].load(Ordering::Relaxed))
+	}
+
+	/// Opens a new channel for captured frames and hands out the audio thread's end; a frame still
+	/// on the way through the old one is lost. A frame is the sample rate, then the input
+	/// magnitudes, then the output ones.
+	pub fn capture_publisher(&self) -> Publisher {
+		let (publisher, reader) = triple_buffer(1 + 2 * self.input.len());
+		*self.capture.lock().unwrap() = Some(reader);
+		publisher
+	}
+
+	/// Asks the audio thread to capture the next frame it processes.
+	pub fn request_capture(&self) {
+		self.capture_requested.store(true, Ordering::Relaxed);
+	}
+
+	/// Whether a capture has been asked for since the last call.
+	pub fn take_capture_request(&self) -> bool {
+		self.capture_requested.swap(false, Ordering::Relaxed)
+	}
+
+	/// The frame captured since the last call, once the audio thread has published it.
+	pub fn take_capture(&self) -> Option<SpectrumCapture> {
+		let mut reader = self.capture.lock().unwrap();
+		let (&sample_rate, bins) = reader.as_mut()?.fresh()?.split_first()?;
+		let (input, output) = bins.split_at(bins.len() / 2);
+		Some(SpectrumCapture {
+			sample_rate,
+			input: input.to_vec(),
+			output: output.to_vec(),
+		})
+	}
+}
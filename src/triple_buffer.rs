@@ -0,0 +1,164 @@
+use std::cell::UnsafeCell;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+// the low bits of `state` are the slot in the middle, this bit says the reader hasn't taken it yet
+const INDEX_MASK: usize = 0b11;
+const FRESH: usize = 0b100;
+
+struct Slot {
+	data: Vec<f32>,
+	len: usize,
+}
+
+/// Three slots handed between one writer and one reader. At any time the writer owns one, the
+/// reader owns another and the third sits in the middle; publishing and reading each swap their
+/// own slot with the middle one, so neither side ever waits on the other.
+struct Shared {
+	slots: [UnsafeCell<Slot>; 3],
+	state: AtomicUsize,
+}
+
+// a slot is only touched by whichever side holds its index, and the indices only change hands
+// through `state`
+unsafe impl Sync for Shared {}
+
+/// Creates a channel that hands frames of up to `capacity` values from the audio thread to the
+/// editor. Everything is allocated here, so publishing never allocates, locks or blocks; the
+/// reader always sees the newest whole frame and frames it was too slow for are skipped.
+pub fn triple_buffer(capacity: usize) -> (Publisher, Reader) {
+	let slot = || UnsafeCell::new(Slot {
+		data: vec![0.0; capacity],
+		len: 0,
+	});
+	let shared = Arc::new(Shared {
+		slots: [slot(), slot(), slot()],
+		state: AtomicUsize::new(1),
+	});
+	(
+		Publisher {
+			shared: shared.clone(),
+			back: 0,
+		},
+		Reader {
+			shared,
+			front: 2,
+			received: false,
+		},
+	)
+}
+
+/// The writing end, kept by the audio thread.
+pub struct Publisher {
+	shared: Arc<Shared>,
+	back: usize,
+}
+
+impl Publisher {
+	/// Makes `frame` the newest one. Anything past the capacity is cut off.
+	pub fn publish(&mut self, frame: &[f32]) {
+		// SAFETY: `back` is only ever held by this side
+		let slot = unsafe { &mut *self.shared.slots[self.back].get() };
+		let len = frame.len().min(slot.data.len());
+		slot.data[..len].copy_from_slice(&frame[..len]);
+		slot.len = len;
+		let old = self.shared.state.swap(self.back | FRESH, Ordering::AcqRel);
+		self.back = old & INDEX_MASK;
+	}
+
+	pub fn capacity(&self) -> usize {
+		// SAFETY: as in `publish`
+		unsafe { (*self.shared.slots[self.back].get()).data.len() }
+	}
+}
+
+/// The reading end, kept by the editor.
+pub struct Reader {
+	shared: Arc<Shared>,
+	front: usize,
+	received: bool,
+}
+
+impl Reader {
+	/// The newest frame published so far, or `None` before the first one.
+	/// Stays the same until another frame comes in.
+	pub fn latest(&mut self) -> Option<&[f32]> {
+		if self.shared.state.load(Ordering::Relaxed) & FRESH != 0 {
+			let old = self.shared.state.swap(self.front, Ordering::AcqRel);
+			self.front = old & INDEX_MASK;
+			self.received = true;
+		}
+		if !self.received {
+			return None;
+		}
+		// SAFETY: `front` is only ever held by this side, and the swap above made the
+		// writer's last changes to it visible
+		let slot = unsafe { &*self.shared.slots[self.front].get() };
+		Some(&slot.data[..slot.len])
+	}
+
+	/// Like [`Reader::latest`], but each frame only once: `None` until another one comes in.
+	pub fn fresh(&mut self) -> Option<&[f32]> {
+		if self.shared.state.load(Ordering::Relaxed) & FRESH == 0 {
+			return None;
+		}
+		self.latest()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const CAPACITY: usize = 64;
+	const FRAMES: usize = 200_000;
+
+	// frame `n` holds n, n + 1, ... and is shorter or longer depending on n, so a frame read while
+	// it's being written over, or put together from two, shows
+	fn frame(n: usize) -> Vec<f32> {
+		(0..1 + n % CAPACITY).map(|i| (n + i) as f32).collect()
+	}
+
+	#[test]
+	fn frames_come_through_whole_while_both_sides_run() {
+		let (mut publisher, mut reader) = triple_buffer(CAPACITY);
+		let frames: Vec<Vec<f32>> = (0..FRAMES).map(frame).collect();
+		let writer = std::thread::spawn(move || {
+			for frame in &frames {
+				publisher.publish(frame);
+			}
+		});
+
+		let mut last = None;
+		let mut seen = 0;
+		while last != Some(FRAMES - 1) {
+			let Some(latest) = reader.latest() else {
+				continue;
+			};
+			let n = latest[0] as usize;
+			assert_eq!(latest, frame(n).as_slice());
+			assert!(last.is_none_or(|last| n >= last), "{n} after {last:?}");
+			if last != Some(n) {
+				seen += 1;
+			}
+			last = Some(n);
+		}
+		writer.join().unwrap();
+		// the reader kept up with some of them at least
+		assert!(seen > 1);
+	}
+
+	#[test]
+	fn fresh_hands_out_each_frame_once() {
+		let (mut publisher, mut reader) = triple_buffer(CAPACITY);
+		assert_eq!(reader.latest(), None);
+		publisher.publish(&[1.0, 2.0]);
+		publisher.publish(&[3.0]);
+		assert_eq!(reader.fresh(), Some([3.0].as_slice()));
+		assert_eq!(reader.fresh(), None);
+		assert_eq!(reader.latest(), Some([3.0].as_slice()));
+		publisher.publish(&vec![4.0; CAPACITY + 1]);
+		assert_eq!(reader.fresh(), Some(vec![4.0; CAPACITY].as_slice()));
+	}
+}
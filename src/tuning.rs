@@ -0,0 +1,85 @@
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use crate::phase_vocoder::BANDS;
+use crate::phase_vocoder::InputParams;
+use crate::phase_vocoder::PhaseVocoder;
+
+/// The frequencies the mapping is probed at for the transposition readout: A1 to A7, an octave
+/// apart, so that every band and most of what a script might treat differently gets a say.
+pub const PROBES: [f32; 7] = [55.0, 110.0, 220.0, 440.0, 880.0, 1760.0, 3520.0];
+// how far apart the probes' transpositions may be and still read as one, in cents
+const TOLERANCE_CENTS: f32 = 1.0;
+
+/// What the mapping does to pitch, worked out from where it sends each of [`PROBES`].
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Transposition {
+	/// Every probe moved by the same number of cents.
+	Uniform(f32),
+	/// The probes moved by different amounts, or one of them was dropped: a frequency shift, a
+	/// snap to a scale, or anything else that isn't a transposition.
+	NonUniform,
+}
+
+impl Transposition {
+	/// From `mapped`, where each of `probes` ended up, `None` if it was dropped.
+	pub fn of(probes: &[f32], mapped: &[Option<f32>]) -> Self {
+		let cents: Option<Vec<f32>> = probes.iter().zip(mapped)
+			.map(|(&probe, &mapped)| mapped.filter(|&mapped| mapped > 0.0).map(|mapped| 1200.0 * (mapped / probe).log2()))
+			.collect();
+		let Some(cents) = cents.filter(|cents| !cents.is_empty()) else {
+			return Transposition::NonUniform;
+		};
+		let lowest = cents.iter().copied().fold(f32::MAX, f32::min);
+		let highest = cents.iter().copied().fold(f32::MIN, f32::max);
+		if highest - lowest > TOLERANCE_CENTS {
+			return Transposition::NonUniform;
+		}
+		Transposition::Uniform((lowest + highest) / 2.0)
+	}
+}
+
+/// `cents` as whole semitones and what's left over, e.g. `+7 st +2.0 ct` for a fifth.
+pub fn format_cents(cents: f32) -> String {
+	let semitones = (cents / 100.0).round();
+	let rest = cents - semitones * 100.0;
+	match (semitones == 0.0, rest.abs() < 0.05) {
+		(true, true) => String::from("0 ct"),
+		(true, false) => format!("{:+.1} ct", rest),
+		(false, true) => format!("{:+} st", semitones),
+		(false, false) => format!("{:+} st {:+.1} ct", semitones, rest),
+	}
+}
+
+/// A processor of the editor's own for the readout next to `pitch_shift`, so the mapping can be
+/// probed without the audio thread. It takes the scripts the way the audio thread's do: one that
+/// doesn't compile leaves the last that did in place.
+#[derive(Default)]
+pub struct TuningProbe {
+	// behind a mutex since the editor's state has to be `Sync`, which a processor isn't
+	vocoder: Mutex<Option<(f32, PhaseVocoder)>>,
+}
+
+impl TuningProbe {
+	/// What `params` and the scripts do to pitch, `None` standing for a script the audio thread
+	/// has rejected. Builds the processor the first time and whenever the sample rate changes.
+	pub fn transposition(&mut self, params: &InputParams, main: Option<&str>, bands: [Option<&str>; BANDS]) -> Transposition {
+		let slot = self.vocoder.get_mut().unwrap_or_else(PoisonError::into_inner);
+		if slot.as_ref().is_some_and(|(sample_rate, _)| *sample_rate != params.sample_rate) {
+			*slot = None;
+		}
+		let (_, vocoder) = slot.get_or_insert_with(|| (params.sample_rate, PhaseVocoder::new(params.window_size, params.sample_rate)));
+		vocoder.renew_window_size(params.window_size);
+		vocoder.renew_zero_pad(params.zero_pad_factor);
+
+		if let Some(code) = main {
+			let _ = vocoder.update_mapping(code);
+		}
+		for (band, code) in bands.into_iter().enumerate() {
+			if let Some(code) = code {
+				let _ = vocoder.update_band_mapping(band, code);
+			}
+		}
+
+		Transposition::of(&PROBES, &vocoder.probe_mapping(params, &PROBES))
+	}
+}